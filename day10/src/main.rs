@@ -1,4 +1,5 @@
 use std::fs;
+use std::env;
 use std::collections::{VecDeque, HashMap};
 use std::time::Instant;
 
@@ -9,7 +10,7 @@ struct Marker {
     num: usize,
 }
 
-fn locate_trailheads(map: &Vec<Vec<usize>>) -> Vec<[usize; 2]> {
+fn locate_trailheads(map: &[Vec<usize>]) -> Vec<[usize; 2]> {
     let num_cols = map[0].len();
     map.iter().for_each(|row| assert_eq!(num_cols, row.len()));
 
@@ -26,14 +27,15 @@ fn locate_trailheads(map: &Vec<Vec<usize>>) -> Vec<[usize; 2]> {
     trailheads
 }
 
-fn get_new_locs(loc: [usize; 2], map: &Vec<Vec<usize>>) -> Vec<[usize; 2]> {
+fn get_new_locs(loc: [usize; 2], map: &[Vec<usize>], diagonal: bool) -> Vec<[usize; 2]> {
     let num_rows = map.len() as isize;
     let num_cols = map[0].len() as isize;
     let [row, col] = loc;
     let [row, col] = [row as isize, col as isize];
-    let steps: [[isize; 2]; 4] = [[-1, 0], [1, 0], [0, 1], [0, -1]];
+    let steps: [[isize; 2]; 8] = [[-1, 0], [1, 0], [0, 1], [0, -1], [-1, -1], [-1, 1], [1, 1], [1, -1]];
+    let num_steps: usize = if diagonal { 8 } else { 4 };
 
-    let valid_new_locs = steps
+    let valid_new_locs = steps[..num_steps]
         .iter()
         .filter(|&[row_step, col_step]| {
             (row+row_step) >= 0 && (row+row_step < num_rows)
@@ -44,7 +46,7 @@ fn get_new_locs(loc: [usize; 2], map: &Vec<Vec<usize>>) -> Vec<[usize; 2]> {
     valid_new_locs
 }
 
-fn trailstart_to_ends(trailhead: &[usize; 2], map: &Vec<Vec<usize>>) -> HashMap<[usize; 2], usize> {
+fn trailstart_to_ends(trailhead: &[usize; 2], map: &[Vec<usize>]) -> HashMap<[usize; 2], usize> {
     let &[row, col] = trailhead;
     let mut queue: VecDeque<Marker> = VecDeque::from(vec![Marker{row, col, num:0}]);
     let mut trailends: HashMap<[usize; 2], usize> = HashMap::new();
@@ -55,7 +57,7 @@ fn trailstart_to_ends(trailhead: &[usize; 2], map: &Vec<Vec<usize>>) -> HashMap<
             *trailends.entry([row, col]).or_insert(0) += 1;
             continue
         }
-        let down_locs: Vec<Marker> = get_new_locs([row, col], map)
+        let down_locs: Vec<Marker> = get_new_locs([row, col], map, false)
             .iter()
             .filter(|&&[row, col]| map[row][col] == num+1)
             .map(|&[row, col]| Marker {row, col, num:num+1 })
@@ -65,6 +67,138 @@ fn trailstart_to_ends(trailhead: &[usize; 2], map: &Vec<Vec<usize>>) -> HashMap<
     trailends
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrailRules {
+    // allowed height change per step, inclusive
+    min_delta: isize,
+    max_delta: isize,
+    start: usize,
+    end: usize,
+    diagonal: bool,
+}
+
+const PUZZLE_RULES: TrailRules = TrailRules { min_delta: 1, max_delta: 1, start: 0, end: 9, diagonal: false };
+
+impl TrailRules {
+    fn allows(&self, from: usize, to: usize) -> bool {
+        (self.min_delta..=self.max_delta).contains(&(to as isize - from as isize))
+    }
+}
+
+// one bit per summit (end height cell), summits numbered in reading order
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SummitSet {
+    words: Vec<u64>,
+}
+
+impl SummitSet {
+    fn new(num_summits: usize) -> Self {
+        SummitSet { words: vec![0; num_summits.div_ceil(64)] }
+    }
+
+    fn insert(&mut self, summit: usize) {
+        self.words[summit / 64] |= 1 << (summit % 64);
+    }
+
+    fn union_with(&mut self, other: &SummitSet) {
+        self.words.iter_mut().zip(other.words.iter()).for_each(|(word, other_word)| *word |= other_word);
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TrailheadSummary {
+    loc: [usize; 2],
+    summits: SummitSet,
+    rating: usize,
+}
+
+// goes through the cells layer by layer, from the end height back to the start height; a cell
+// reaches the union of the summits of the neighbours it can step to, and has the sum of their
+// trail counts. A trail stops at the first cell of end height. Summit sets are dropped as soon as
+// no later layer can step to them
+fn count_trails(map: &[Vec<usize>], rules: &TrailRules) -> Result<Vec<TrailheadSummary>, &'static str> {
+    // the layer which can no longer be stepped to, once a layer is done
+    let farthest_delta: isize = if rules.min_delta > 0 {
+        rules.max_delta
+    } else if rules.max_delta < 0 {
+        rules.min_delta
+    } else {
+        return Err("Trails can loop unless every step climbs or every step descends")
+    };
+
+    let num_cols: usize = map[0].len();
+    let heights: Vec<usize> = map.iter().flatten().copied().collect();
+    let num_heights: usize = heights.iter().max().map_or(0, |&height| height + 1).max(rules.start + 1);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); num_heights];
+    heights.iter().enumerate().for_each(|(index, &height)| layers[height].push(index));
+    let layer_order: Vec<usize> = if farthest_delta > 0 { (0..num_heights).rev().collect() } else { (0..num_heights).collect() };
+
+    let num_summits: usize = layers.get(rules.end).map_or(0, |layer| layer.len());
+    let mut summits: Vec<Option<SummitSet>> = vec![None; heights.len()];
+    let mut ratings: Vec<usize> = vec![0; heights.len()];
+
+    for height in layer_order {
+        for (i, &index) in layers[height].iter().enumerate() {
+            let mut summit_set = SummitSet::new(num_summits);
+            if height == rules.end {
+                summit_set.insert(i);
+                ratings[index] = 1;
+                summits[index] = Some(summit_set);
+                continue
+            }
+            for [row, col] in get_new_locs([index / num_cols, index % num_cols], map, rules.diagonal) {
+                let next: usize = row * num_cols + col;
+                if !rules.allows(height, heights[next]) { continue }
+                if let Some(next_set) = &summits[next] { summit_set.union_with(next_set) }
+                ratings[index] += ratings[next];
+            }
+            summits[index] = Some(summit_set);
+        }
+        let done_height: isize = height as isize + farthest_delta;
+        if done_height >= 0 && (done_height as usize) < num_heights && done_height as usize != rules.start {
+            layers[done_height as usize].iter().for_each(|&index| summits[index] = None);
+        }
+    }
+
+    Ok(layers[rules.start].iter()
+        .map(|&index| TrailheadSummary {
+            loc: [index / num_cols, index % num_cols],
+            summits: summits[index].take().unwrap(),
+            rating: ratings[index],
+        })
+        .collect())
+}
+
+fn extend_trails(map: &[Vec<usize>], rules: &TrailRules, limit: usize, path: &mut Vec<[usize; 2]>, trails: &mut Vec<Vec<[usize; 2]>>) {
+    let [row, col] = *path.last().unwrap();
+    if map[row][col] == rules.end {
+        trails.push(path.clone());
+        return
+    }
+    for next in get_new_locs([row, col], map, rules.diagonal) {
+        if trails.len() >= limit { return }
+        if !rules.allows(map[row][col], map[next[0]][next[1]]) || path.contains(&next) { continue }
+        path.push(next);
+        extend_trails(map, rules, limit, path, trails);
+        path.pop();
+    }
+}
+
+// depth first, so trails sharing a beginning come out together; a trail never visits a cell twice,
+// which keeps this finite for rules with level or mixed steps
+fn enumerate_trails(map: &[Vec<usize>], rules: &TrailRules, trailhead: [usize; 2], limit: usize) -> Vec<Vec<[usize; 2]>> {
+    let mut trails: Vec<Vec<[usize; 2]>> = Vec::new();
+    let [row, col] = trailhead;
+    if map[row][col] == rules.start && limit > 0 {
+        extend_trails(map, rules, limit, &mut vec![trailhead], &mut trails);
+    }
+    trails
+}
+
 fn main() {
     let filename: &str = "input.txt";
     let textdata: String = fs::read_to_string(filename)
@@ -76,6 +210,39 @@ fn main() {
         .map(|line| line.chars().map(|chr| chr.to_digit(10).unwrap() as usize).collect())
         .collect();
 
+    let args: Vec<String> = env::args().collect();
+    let flag_values = |flag: &str, num: usize| -> Option<Vec<isize>> {
+        let i_flag: usize = args.iter().position(|arg| arg == flag)?;
+        Some((1..=num)
+            .map(|i| args.get(i_flag + i).and_then(|arg| arg.parse().ok()).unwrap_or_else(|| panic!("{flag} needs {num} numbers")))
+            .collect())
+    };
+    let mut rules: TrailRules = PUZZLE_RULES;
+    if let Some(values) = flag_values("--rules", 4) {
+        rules = TrailRules { min_delta: values[0], max_delta: values[1], start: values[2] as usize, end: values[3] as usize, ..rules };
+    }
+    rules.diagonal = args.iter().any(|arg| arg == "--diagonal");
+
+    if let Some(values) = flag_values("--trails", 3) {
+        for trail in enumerate_trails(&map, &rules, [values[0] as usize, values[1] as usize], values[2] as usize) {
+            let steps: Vec<String> = trail.iter().map(|[row, col]| format!("({row},{col})")).collect();
+            println!("{}", steps.join(" -> "));
+        }
+        return
+    }
+
+    if !args.iter().any(|arg| arg == "--bfs") {
+        let summaries: Vec<TrailheadSummary> = count_trails(&map, &rules).unwrap_or_else(|err| panic!("{err}"));
+        let trailhead_score: usize = summaries.iter().map(|summary| summary.summits.len()).sum();
+        let unique_score: usize = summaries.iter().map(|summary| summary.rating).sum();
+
+        let after = before.elapsed();
+        println!("(Part  I) trailhead score: {trailhead_score}");
+        println!("(Part II) unique score: {unique_score}");
+        println!("(Parts I & II) elapsed time: {after:.2?}");
+        return
+    }
+
     let trailheads: Vec<[usize; 2]> = locate_trailheads(&map);
     let trailends_hashmaps: Vec<HashMap<[usize; 2], usize>> = trailheads.iter()
         .map(|loc| trailstart_to_ends(loc, &map))
@@ -142,3 +309,97 @@ fn test_locate_trailends() {
         .sum();
     assert_eq!(36, trailhead_score);
 }
+
+#[test]
+fn test_count_trails() {
+    let map = vec![
+        vec![8,9,0,1,0,1,2,3,],
+        vec![7,8,1,2,1,8,7,4,],
+        vec![8,7,4,3,0,9,6,5,],
+        vec![9,6,5,4,9,8,7,4,],
+        vec![4,5,6,7,8,9,0,3,],
+        vec![3,2,0,1,9,0,1,2,],
+        vec![0,1,3,2,9,8,0,1,],
+        vec![1,0,4,5,6,7,3,2,],
+    ];
+    let summaries: Vec<TrailheadSummary> = count_trails(&map, &PUZZLE_RULES).unwrap();
+    assert_eq!(locate_trailheads(&map), summaries.iter().map(|summary| summary.loc).collect::<Vec<_>>());
+    assert_eq!(36, summaries.iter().map(|summary| summary.summits.len()).sum::<usize>());
+    assert_eq!(81, summaries.iter().map(|summary| summary.rating).sum::<usize>());
+
+    // per trailhead, the same as the search from that trailhead
+    for summary in summaries.iter() {
+        let trailends: HashMap<[usize; 2], usize> = trailstart_to_ends(&summary.loc, &map);
+        assert_eq!(trailends.len(), summary.summits.len());
+        assert_eq!(trailends.values().sum::<usize>(), summary.rating);
+    }
+
+    // more than 64 summits, each trailhead reaches only the summit of its own row
+    let map: Vec<Vec<usize>> = vec![(0..10).collect(); 100];
+    let summaries: Vec<TrailheadSummary> = count_trails(&map, &PUZZLE_RULES).unwrap();
+    assert_eq!(100, summaries.len());
+    let mut row_summit = SummitSet::new(100);
+    row_summit.insert(70);
+    assert_eq!(row_summit, summaries[70].summits);
+    assert!(summaries.iter().all(|summary| summary.summits.len() == 1 && summary.rating == 1));
+}
+
+#[test]
+fn test_trail_rules() {
+    let map = vec![
+        vec![8,9,0,1,0,1,2,3,],
+        vec![7,8,1,2,1,8,7,4,],
+        vec![8,7,4,3,0,9,6,5,],
+        vec![9,6,5,4,9,8,7,4,],
+        vec![4,5,6,7,8,9,0,3,],
+        vec![3,2,0,1,9,0,1,2,],
+        vec![0,1,3,2,9,8,0,1,],
+        vec![1,0,4,5,6,7,3,2,],
+    ];
+
+    // the first trailhead has a rating of 20
+    let trails: Vec<Vec<[usize; 2]>> = enumerate_trails(&map, &PUZZLE_RULES, [0, 2], 100);
+    assert_eq!(20, trails.len());
+    for trail in trails.iter() {
+        assert_eq!(10, trail.len());
+        assert!(trail.iter().enumerate().all(|(height, &[row, col])| map[row][col] == height));
+        assert!(trail.windows(2).all(|pair| pair[0][0].abs_diff(pair[1][0]) + pair[0][1].abs_diff(pair[1][1]) == 1));
+    }
+    assert_eq!(trails[..5], enumerate_trails(&map, &PUZZLE_RULES, [0, 2], 5));
+    assert!(enumerate_trails(&map, &PUZZLE_RULES, [0, 0], 5).is_empty());
+
+    // descending from the summits back to the trailheads gives the same number of trails
+    let down_rules = TrailRules { min_delta: -1, max_delta: -1, start: 9, end: 0, diagonal: false };
+    let summaries: Vec<TrailheadSummary> = count_trails(&map, &down_rules).unwrap();
+    assert_eq!(81, summaries.iter().map(|summary| summary.rating).sum::<usize>());
+    assert_eq!(36, summaries.iter().map(|summary| summary.summits.len()).sum::<usize>());
+
+    // diagonal steps and bigger steps only add trails; counting agrees with enumerating
+    for rules in [
+        TrailRules { diagonal: true, ..PUZZLE_RULES },
+        TrailRules { max_delta: 3, ..PUZZLE_RULES },
+        TrailRules { min_delta: 2, max_delta: 3, start: 1, end: 8, diagonal: true },
+    ] {
+        let summaries: Vec<TrailheadSummary> = count_trails(&map, &rules).unwrap();
+        for summary in summaries.iter() {
+            let trails: Vec<Vec<[usize; 2]>> = enumerate_trails(&map, &rules, summary.loc, usize::MAX);
+            assert_eq!(trails.len(), summary.rating);
+            let mut ends: Vec<[usize; 2]> = trails.iter().map(|trail| *trail.last().unwrap()).collect();
+            ends.sort();
+            ends.dedup();
+            assert_eq!(ends.len(), summary.summits.len());
+        }
+    }
+    let summaries: Vec<TrailheadSummary> = count_trails(&map, &TrailRules { diagonal: true, ..PUZZLE_RULES }).unwrap();
+    assert!(summaries.iter().map(|summary| summary.rating).sum::<usize>() > 81);
+
+    // level steps can loop, so only enumeration (of simple paths) works for them
+    let level_rules = TrailRules { min_delta: 0, max_delta: 1, ..PUZZLE_RULES };
+    assert!(count_trails(&map, &level_rules).is_err());
+    let flat = [vec![0, 0, 1], vec![0, 0, 9]];
+    let level_rules = TrailRules { min_delta: 0, max_delta: 8, ..PUZZLE_RULES };
+    assert_eq!(vec![
+        vec![[0, 0], [1, 0], [1, 1], [0, 1], [0, 2], [1, 2]],
+        vec![[0, 0], [0, 1], [0, 2], [1, 2]],
+    ], enumerate_trails(&flat, &level_rules, [0, 0], 10));
+}
@@ -1,45 +1,380 @@
 use std::fs;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::env;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 
-fn get_child(rock: &usize) -> [Option<usize>; 2] {
-    let is_zero: bool = rock==&0 ;
-    if is_zero { return [Some(1), None] }
-    let num_digits: u32 = match rock.checked_ilog10() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Predicate {
+    Is(usize),
+    DigitsDivisibleBy(u32),
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transform {
+    Replace(usize),
+    // into equal parts by digits; the leftmost part takes any extra digits
+    Split(u32),
+    Multiply(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StoneRule {
+    when: Predicate,
+    then: Transform,
+}
+
+const PUZZLE_RULES: [StoneRule; 3] = [
+    StoneRule { when: Predicate::Is(0), then: Transform::Replace(1) },
+    StoneRule { when: Predicate::DigitsDivisibleBy(2), then: Transform::Split(2) },
+    StoneRule { when: Predicate::Any, then: Transform::Multiply(2024) },
+];
+
+fn num_digits(rock: usize) -> u32 {
+    match rock.checked_ilog10() {
         Some(val) => val + 1,
         None => 1,
-    };
-    let has_even_digits: bool = num_digits % 2 == 0;
-    if has_even_digits {
-        let power_ten = 10usize.pow((num_digits/2) as u32 );
-        let right_rock = rock % power_ten as usize ;
-        let left_rock = rock / power_ten as usize ;
-        return [Some(left_rock), Some(right_rock)]
     }
-    return [Some(rock * 2024), None]
 }
 
-fn sum_after_blinks(rocks: Vec<usize>, blinks: usize) -> usize {
+impl Predicate {
+    fn matches(&self, rock: usize) -> bool {
+        match *self {
+            Predicate::Is(num) => rock == num,
+            Predicate::DigitsDivisibleBy(divisor) => num_digits(rock).is_multiple_of(divisor),
+            Predicate::Any => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StoneError {
+    // the stone which would grow past usize
+    ValueOverflow(usize),
+    // more stones than fit in usize
+    CountOverflow,
+}
+
+impl Transform {
+    fn apply(&self, rock: usize) -> Result<Vec<usize>, StoneError> {
+        match *self {
+            Transform::Replace(num) => Ok(vec![num]),
+            Transform::Split(num_parts) => {
+                let part_digits: u32 = num_digits(rock) / num_parts.max(1);
+                if part_digits == 0 { return Ok(vec![rock]) }
+                // only a single part can have 20 digits, and then it is the whole stone
+                let Some(power_ten) = 10usize.checked_pow(part_digits) else { return Ok(vec![rock]) };
+                let mut rest: usize = rock;
+                let mut parts: Vec<usize> = (1..num_parts)
+                    .map(|_| {
+                        let part = rest % power_ten;
+                        rest /= power_ten;
+                        part
+                    })
+                    .collect();
+                parts.push(rest);
+                parts.reverse();
+                Ok(parts)
+            },
+            Transform::Multiply(factor) => Ok(vec![rock.checked_mul(factor).ok_or(StoneError::ValueOverflow(rock))?]),
+        }
+    }
+}
+
+fn get_children(rock: usize, rules: &[StoneRule]) -> Result<Vec<usize>, StoneError> {
+    match rules.iter().find(|rule| rule.when.matches(rock)) {
+        Some(rule) => rule.then.apply(rock),
+        None => Ok(vec![rock]),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>, line: &str) -> Result<T, String> {
+    word.and_then(|word| word.parse().ok()).ok_or_else(|| format!("Missing or bad number in rule ({line})"))
+}
+
+// one rule per line, `<predicate> -> <transform>`, with the predicates `is N`,
+// `digits-divisible-by K` and `any`, and the transforms `replace N`, `split K` and `multiply K`.
+// Empty lines and lines starting with `#` are skipped
+fn parse_rules(text: &str) -> Result<Vec<StoneRule>, String> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (when, then) = line.split_once("->").ok_or_else(|| format!("Rule without -> ({line})"))?;
+            let mut when_words = when.split_whitespace();
+            let when: Predicate = match when_words.next() {
+                Some("is") => Predicate::Is(parse_number(when_words.next(), line)?),
+                Some("digits-divisible-by") => Predicate::DigitsDivisibleBy(parse_number(when_words.next(), line)?),
+                Some("any") => Predicate::Any,
+                _ => return Err(format!("Unknown predicate ({line})")),
+            };
+            let mut then_words = then.split_whitespace();
+            let then: Transform = match then_words.next() {
+                Some("replace") => Transform::Replace(parse_number(then_words.next(), line)?),
+                Some("split") => Transform::Split(parse_number(then_words.next(), line)?),
+                Some("multiply") => Transform::Multiply(parse_number(then_words.next(), line)?),
+                _ => return Err(format!("Unknown transform ({line})")),
+            };
+            if when_words.next().is_some() || then_words.next().is_some() { return Err(format!("Trailing words in rule ({line})")) }
+            Ok(StoneRule { when, then })
+        })
+        .collect()
+}
+
+// number of stones of each value after the blinks; an error if a stone outgrows usize, which
+// user-given rules can easily do
+fn stone_distribution(rocks: &[usize], blinks: usize, rules: &[StoneRule]) -> Result<HashMap<usize, usize>, StoneError> {
 
     let mut parent_map: HashMap<usize, usize> = HashMap::new();
     rocks
         .iter()
-        .for_each(|&rock| {parent_map.insert(rock, 1);} );
+        .for_each(|&rock| *parent_map.entry(rock).or_insert(0) += 1);
 
     for _ in 0..blinks {
         let mut child_map: HashMap<usize, usize> = HashMap::new();
 
-        parent_map.iter()
-            .flat_map(|(parent, &num)| get_child(parent)
-                .into_iter()
-                .filter_map(|child| child)
-                .map(move|child| [child, num])
-            )
-            .for_each(|[child, num]| {*child_map.entry(child).or_insert(0) += num;});
+        for (&parent, &num) in parent_map.iter() {
+            for child in get_children(parent, rules)? {
+                let count: &mut usize = child_map.entry(child).or_insert(0);
+                *count = count.checked_add(num).ok_or(StoneError::CountOverflow)?;
+            }
+        }
+        parent_map = child_map;
+    }
+
+    Ok(parent_map)
+}
+
+fn sum_after_blinks(rocks: Vec<usize>, blinks: usize, rules: &[StoneRule]) -> Result<usize, StoneError> {
+    stone_distribution(&rocks, blinks, rules)?.values()
+        .try_fold(0usize, |total, &count| total.checked_add(count))
+        .ok_or(StoneError::CountOverflow)
+}
+
+// same as stone_distribution, with the counts kept modulo the modulus; stones with a count of 0
+// (modulo) are left out
+fn stone_distribution_mod(rocks: &[usize], blinks: u64, rules: &[StoneRule], modulus: u64) -> Result<HashMap<usize, u64>, StoneError> {
+    let mut parent_map: HashMap<usize, u64> = HashMap::new();
+    rocks.iter().for_each(|&rock| *parent_map.entry(rock).or_insert(0) += 1);
+    parent_map.values_mut().for_each(|count| *count %= modulus);
+
+    for _ in 0..blinks {
+        let mut child_map: HashMap<usize, u64> = HashMap::new();
+        for (&parent, &num) in parent_map.iter() {
+            for child in get_children(parent, rules)? {
+                let count: &mut u64 = child_map.entry(child).or_insert(0);
+                *count = ((*count as u128 + num as u128) % modulus as u128) as u64;
+            }
+        }
         parent_map = child_map;
     }
 
-    parent_map.values().sum()
+    parent_map.retain(|_, count| *count != 0);
+    Ok(parent_map)
+}
+
+// all stone values which can ever appear from these stones, sorted; None when there are more than
+// max_size of them (or a stone outgrows usize), as some rule sets keep making new values forever
+fn closed_stone_set(rocks: &[usize], rules: &[StoneRule], max_size: usize) -> Option<Vec<usize>> {
+    let mut seen: HashSet<usize> = rocks.iter().copied().collect();
+    let mut stack: Vec<usize> = seen.iter().copied().collect();
+    while let Some(rock) = stack.pop() {
+        for child in get_children(rock, rules).ok()? {
+            if seen.insert(child) { stack.push(child) }
+        }
+        if seen.len() > max_size { return None }
+    }
+    let mut stones: Vec<usize> = seen.into_iter().collect();
+    stones.sort();
+    Some(stones)
+}
+
+// per value of the closed set, the indices of the values one such stone turns into (a sparse
+// transition matrix, as a stone has only a few children)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TransitionMatrix {
+    stones: Vec<usize>,
+    children: Vec<Vec<usize>>,
+}
+
+fn transition_matrix(stones: &[usize], rules: &[StoneRule]) -> TransitionMatrix {
+    let index: HashMap<usize, usize> = stones.iter().enumerate().map(|(i, &stone)| (stone, i)).collect();
+    let children: Vec<Vec<usize>> = stones.iter()
+        .map(|&stone| get_children(stone, rules).unwrap().iter()
+            .map(|child| *index.get(child).unwrap_or_else(|| panic!("Stone {child} is not in the closed set")))
+            .collect()
+        )
+        .collect();
+    TransitionMatrix { stones: stones.to_vec(), children }
+}
+
+fn mul_mod(num1: u64, num2: u64, modulus: u64) -> u64 {
+    (num1 as u128 * num2 as u128 % modulus as u128) as u64
+}
+
+// both numbers at most the modulus
+fn add_mod(num1: u64, num2: u64, modulus: u64) -> u64 {
+    let sum: u128 = num1 as u128 + num2 as u128;
+    if sum >= modulus as u128 { (sum - modulus as u128) as u64 } else { sum as u64 }
+}
+
+impl TransitionMatrix {
+    // the counts (indexed like stones) after one more blink
+    fn blink_mod(&self, counts: &[u64], modulus: u64) -> Vec<u64> {
+        let mut next_counts: Vec<u64> = vec![0; counts.len()];
+        for (children, &count) in self.children.iter().zip(counts.iter()).filter(|(_, &count)| count != 0) {
+            for &child in children {
+                next_counts[child] = add_mod(next_counts[child], count, modulus);
+            }
+        }
+        next_counts
+    }
+
+    // counts[0], counts[1], ... of the stones blinking from the given counts
+    fn blinks_mod(&self, counts: Vec<u64>, modulus: u64) -> impl Iterator<Item = Vec<u64>> + '_ {
+        std::iter::successors(Some(counts), move |counts| Some(self.blink_mod(counts, modulus)))
+    }
+}
+
+// the recurrences are found modulo this prime, and then carried over to any modulus
+const RECURRENCE_PRIME: u64 = (1 << 61) - 1;
+
+fn pow_mod(base: u64, exponent: u64, modulus: u64) -> u64 {
+    let (mut base, mut exponent, mut power) = (base, exponent, 1 % modulus);
+    while exponent > 0 {
+        if exponent % 2 == 1 { power = mul_mod(power, base, modulus) }
+        base = mul_mod(base, base, modulus);
+        exponent /= 2;
+    }
+    power
+}
+
+// Berlekamp-Massey: the shortest [1, c1, .., cL] with seq[k] + c1 seq[k-1] + .. + cL seq[k-L] = 0
+// for all k >= L, modulo a prime
+fn shortest_recurrence(seq: &[u64], prime: u64) -> Vec<u64> {
+    let mut current: Vec<u64> = vec![1];
+    let mut previous: Vec<u64> = vec![1];
+    let mut len: usize = 0;
+    let mut shift: usize = 1;
+    let mut previous_discrepancy: u64 = 1;
+    for k in 0..seq.len() {
+        let discrepancy: u64 = (0..=len).fold(0, |sum, i| add_mod(sum, mul_mod(current[i], seq[k - i], prime), prime));
+        if discrepancy == 0 {
+            shift += 1;
+            continue
+        }
+        let factor: u64 = mul_mod(discrepancy, pow_mod(previous_discrepancy, prime - 2, prime), prime);
+        let before: Vec<u64> = current.clone();
+        current.resize(current.len().max(previous.len() + shift), 0);
+        for (i, &coef) in previous.iter().enumerate() {
+            current[i + shift] = add_mod(current[i + shift], prime - mul_mod(factor, coef, prime), prime);
+        }
+        if 2 * len <= k {
+            len = k + 1 - len;
+            previous = before;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+    current.truncate(len + 1);
+    current
+}
+
+// x^exponent modulo the monic x^L + c1 x^(L-1) + .. + cL, given as [1, c1, .., cL]; the remainder
+// has the coefficients of x^0 .. x^(L-1)
+fn pow_x_mod(exponent: u64, recurrence: &[u64], modulus: u64) -> Vec<u64> {
+    let len: usize = recurrence.len() - 1;
+    let reduce = |mut poly: Vec<u64>| -> Vec<u64> {
+        for degree in (len..poly.len()).rev() {
+            let coef: u64 = poly[degree];
+            if coef == 0 { continue }
+            for (i, &rec_coef) in recurrence.iter().enumerate().skip(1) {
+                poly[degree - i] = add_mod(poly[degree - i], modulus - mul_mod(coef, rec_coef, modulus), modulus);
+            }
+        }
+        poly.truncate(len);
+        poly
+    };
+
+    let mut power: Vec<u64> = reduce(vec![1 % modulus]);
+    for bit in (0..u64::BITS - exponent.leading_zeros()).rev() {
+        let mut square: Vec<u64> = vec![0; (2 * power.len()).saturating_sub(1)];
+        for (i, &coef1) in power.iter().enumerate().filter(|(_, &coef)| coef != 0) {
+            for (j, &coef2) in power.iter().enumerate() {
+                square[i + j] = add_mod(square[i + j], mul_mod(coef1, coef2, modulus), modulus);
+            }
+        }
+        if (exponent >> bit) & 1 == 1 { square.insert(0, 0) }
+        power = reduce(square);
+    }
+    power
+}
+
+// closed sets of real puzzle inputs have a few thousand values
+const MAX_CLOSED_VALUES: usize = 100_000;
+// blinking one at a time instead is only done up to this many blinks
+const MAX_HISTOGRAM_BLINKS: u64 = 1_000_000;
+
+// the counts are kept modulo the modulus, so any number of blinks works. The counts after each
+// blink follow a linear recurrence, of at most as many terms as there are values in the closed
+// set: it is found (by Berlekamp-Massey) on a random mix of the counts, checked to hold for all the
+// counts, and then the blinks are raised as a polynomial modulo it. None if the closed set has over
+// max_size values, or the recurrence does not carry over to the modulus
+fn distribution_mod(rocks: &[usize], blinks: u64, rules: &[StoneRule], modulus: u64, max_size: usize) -> Option<HashMap<usize, u64>> {
+    let stones: Vec<usize> = closed_stone_set(rocks, rules, max_size)?;
+    let matrix: TransitionMatrix = transition_matrix(&stones, rules);
+    let start_counts = |modulus: u64| -> Vec<u64> {
+        let mut counts: Vec<u64> = vec![0; stones.len()];
+        for &rock in rocks {
+            let i: usize = stones.binary_search(&rock).unwrap();
+            counts[i] = add_mod(counts[i], 1, modulus);
+        }
+        counts
+    };
+
+    // fixed pseudo-random weights, so that no part of the counts is left out of the mix
+    let weights: Vec<u64> = (1..=stones.len() as u64)
+        .map(|i| mul_mod(i, 0x9E37_79B9_7F4A_7C15, RECURRENCE_PRIME))
+        .collect();
+    let mix: Vec<u64> = matrix.blinks_mod(start_counts(RECURRENCE_PRIME), RECURRENCE_PRIME)
+        .take(2 * stones.len() + 1)
+        .map(|counts| counts.iter().zip(weights.iter())
+            .fold(0, |sum, (&count, &weight)| add_mod(sum, mul_mod(count, weight, RECURRENCE_PRIME), RECURRENCE_PRIME))
+        )
+        .collect();
+    // the true recurrence has integer coefficients, small enough here to read off as -p/2 .. p/2
+    let recurrence: Vec<u64> = shortest_recurrence(&mix, RECURRENCE_PRIME).into_iter()
+        .map(|coef| match coef > RECURRENCE_PRIME / 2 {
+            true => (modulus - (RECURRENCE_PRIME - coef) % modulus) % modulus,
+            false => coef % modulus,
+        })
+        .collect();
+    let len: usize = recurrence.len() - 1;
+
+    // holding for the first len + 1 counts, it holds for all later ones too
+    let mut check: Vec<u64> = vec![0; stones.len()];
+    for (k, counts) in matrix.blinks_mod(start_counts(modulus), modulus).take(len + 1).enumerate() {
+        for (sum, &count) in check.iter_mut().zip(counts.iter()) {
+            *sum = add_mod(*sum, mul_mod(recurrence[len - k], count, modulus), modulus);
+        }
+    }
+    if check.iter().any(|&sum| sum != 0) { return None }
+
+    let coefs: Vec<u64> = pow_x_mod(blinks, &recurrence, modulus);
+    let mut final_counts: Vec<u64> = vec![0; stones.len()];
+    for (&coef, counts) in coefs.iter().zip(matrix.blinks_mod(start_counts(modulus), modulus)) {
+        for (sum, &count) in final_counts.iter_mut().zip(counts.iter()) {
+            *sum = add_mod(*sum, mul_mod(coef, count, modulus), modulus);
+        }
+    }
+
+    Some(stones.into_iter()
+        .zip(final_counts)
+        .filter(|&(_, count)| count != 0)
+        .collect())
 }
  
 fn main() {
@@ -47,6 +382,55 @@ fn main() {
     let filedata: String = fs::read_to_string(filename)
         .unwrap_or_else(|_| panic!("Cannot read {filename}"));
 
+    let args: Vec<String> = env::args().collect();
+    let rules: Vec<StoneRule> = match args.iter().position(|arg| arg == "--rules") {
+        Some(i_rules) => {
+            let rules_filename: &str = args.get(i_rules+1).expect("--rules needs a file name");
+            let rules_text: String = fs::read_to_string(rules_filename)
+                .unwrap_or_else(|_| panic!("Cannot read {rules_filename}"));
+            parse_rules(&rules_text).unwrap_or_else(|err| panic!("{err}"))
+        },
+        None => PUZZLE_RULES.to_vec(),
+    };
+
+    if let Some(i_histogram) = args.iter().position(|arg| arg == "--histogram") {
+        let blinks: usize = args.get(i_histogram+1).and_then(|arg| arg.parse().ok()).expect("--histogram needs a number of blinks");
+        let rocks: Vec<usize> = filedata.split_whitespace().map(|string| string.parse::<usize>().unwrap()).collect();
+        let mut histogram: Vec<(usize, usize)> = match stone_distribution(&rocks, blinks, &rules) {
+            Ok(distribution) => distribution.into_iter().collect(),
+            Err(err) => {
+                println!("cannot blink {blinks} times: {err:?}");
+                return
+            },
+        };
+        histogram.sort_by_key(|&(stone, count)| (Reverse(count), stone));
+        histogram.iter().for_each(|(stone, count)| println!("{stone:>20}: {count}"));
+        return
+    }
+    if let Some(i_mod) = args.iter().position(|arg| arg == "--blinks-mod") {
+        let [blinks, modulus]: [u64; 2] = [1, 2].map(|i| args.get(i_mod+i).and_then(|arg| arg.parse().ok()).expect("--blinks-mod needs a number of blinks and a modulus"));
+        if modulus == 0 {
+            println!("--blinks-mod needs a modulus of at least 1");
+            return
+        }
+        let rocks: Vec<usize> = filedata.split_whitespace().map(|string| string.parse::<usize>().unwrap()).collect();
+        let before = Instant::now();
+        let distribution: Result<HashMap<usize, u64>, String> = match distribution_mod(&rocks, blinks, &rules, modulus, MAX_CLOSED_VALUES) {
+            Some(distribution) => Ok(distribution),
+            None if blinks <= MAX_HISTOGRAM_BLINKS => stone_distribution_mod(&rocks, blinks, &rules, modulus).map_err(|err| format!("{err:?}")),
+            None => Err(format!("no recurrence over a closed set of at most {MAX_CLOSED_VALUES} values, and over {MAX_HISTOGRAM_BLINKS} blinks to do one at a time")),
+        };
+        match distribution {
+            Ok(distribution) => {
+                let total: u64 = distribution.values().fold(0, |total, &count| ((total as u128 + count as u128) % modulus as u128) as u64);
+                println!("num of rocks after {blinks} blinks, modulo {modulus}: {total}");
+            },
+            Err(err) => println!("cannot blink {blinks} times: {err}"),
+        }
+        println!("elapsed time: {:.2?}", before.elapsed());
+        return
+    }
+
     let before = Instant::now();
 
     let blinks = 25;
@@ -54,10 +438,13 @@ fn main() {
         .split_whitespace()
         .map(|string| string.parse::<usize>().unwrap())
         .collect();
-    let num_after_blinks: usize = sum_after_blinks(rocks, blinks);
+    let num_after_blinks: Result<usize, StoneError> = sum_after_blinks(rocks, blinks, &rules);
 
     let after = before.elapsed();
-    println!("(Part  I) num of rocks after blinks: {num_after_blinks}");
+    match num_after_blinks {
+        Ok(num_after_blinks) => println!("(Part  I) num of rocks after blinks: {num_after_blinks}"),
+        Err(err) => println!("(Part  I) cannot blink {blinks} times: {err:?}"),
+    }
     println!("(Part  I) elapsed time: {after:.2?}");
     let before = Instant::now();
 
@@ -66,10 +453,13 @@ fn main() {
         .split_whitespace()
         .map(|string| string.parse::<usize>().unwrap())
         .collect();
-    let num_after_blinks: usize = sum_after_blinks(rocks, blinks);
+    let num_after_blinks: Result<usize, StoneError> = sum_after_blinks(rocks, blinks, &rules);
 
     let after = before.elapsed();
-    println!("(Part II) num of rocks after blinks: {num_after_blinks}");
+    match num_after_blinks {
+        Ok(num_after_blinks) => println!("(Part II) num of rocks after blinks: {num_after_blinks}"),
+        Err(err) => println!("(Part II) cannot blink {blinks} times: {err:?}"),
+    }
     println!("(Part II) elapsed time: {after:.2?}");
 }
 
@@ -78,10 +468,121 @@ fn test_hashmap() {
     let blinks = 6;
 
     let start_rock: Vec<usize> = vec![125];
-    let num_after_blinks: usize = sum_after_blinks(start_rock, blinks);
+    let num_after_blinks: usize = sum_after_blinks(start_rock, blinks, &PUZZLE_RULES).unwrap();
     assert_eq!(7, num_after_blinks);
 
     let start_rocks: Vec<usize> = vec![125, 17];
-    let num_after_blinks: usize = sum_after_blinks(start_rocks, blinks);
+    let num_after_blinks: usize = sum_after_blinks(start_rocks, blinks, &PUZZLE_RULES).unwrap();
     assert_eq!(22, num_after_blinks);
 }
+
+#[test]
+fn test_rules() {
+    let text: String = fs::read_to_string("puzzle_rules.txt").unwrap();
+    assert_eq!(PUZZLE_RULES.to_vec(), parse_rules(&text).unwrap());
+
+    assert_eq!(vec![1], get_children(0, &PUZZLE_RULES).unwrap());
+    assert_eq!(vec![10, 0], get_children(1000, &PUZZLE_RULES).unwrap());
+    assert_eq!(vec![2024 * 125], get_children(125, &PUZZLE_RULES).unwrap());
+    assert_eq!(55312, sum_after_blinks(vec![125, 17], 25, &PUZZLE_RULES).unwrap());
+
+    // the first matching rule wins, stones which match nothing stay
+    let rules: Vec<StoneRule> = parse_rules("
+        # thirds, then halves
+        digits-divisible-by 3 -> split 3
+        digits-divisible-by 2 -> split 2
+        is 7 -> multiply 11
+    ").unwrap();
+    assert_eq!(vec![12, 34, 56], get_children(123456, &rules).unwrap());
+    assert_eq!(vec![1, 2, 3], get_children(123, &rules).unwrap());
+    assert_eq!(vec![12, 3], get_children(1203, &rules).unwrap());
+    assert_eq!(vec![77], get_children(7, &rules).unwrap());
+    assert_eq!(vec![5], get_children(5, &rules).unwrap());
+    // 7 -> 77 -> 7 7 -> 77 77 -> 7 7 7 7
+    assert_eq!(4, sum_after_blinks(vec![7], 4, &rules).unwrap());
+    assert_eq!(9, sum_after_blinks(vec![7, 7, 5], 4, &rules).unwrap());
+
+    // the leftmost part takes the extra digits
+    assert_eq!(vec![123, 45, 67], Transform::Split(3).apply(1234567).unwrap());
+    assert_eq!(vec![4], Transform::Split(3).apply(4).unwrap());
+    assert_eq!(vec![10_000_000_000_000_000_000], Transform::Split(1).apply(10_000_000_000_000_000_000).unwrap());
+    assert_eq!(vec![1234], Transform::Split(1).apply(1234).unwrap());
+
+    assert!(parse_rules("is 0").is_err());
+    assert!(parse_rules("odd -> replace 1").is_err());
+    assert!(parse_rules("is x -> replace 1").is_err());
+    assert!(parse_rules("any -> multiply 2 3").is_err());
+
+    // growing past usize is an error, not a panic
+    let rules: Vec<StoneRule> = parse_rules("any -> multiply 1000000000000").unwrap();
+    assert_eq!(Err(StoneError::ValueOverflow(1_000_000_000_000)), sum_after_blinks(vec![1], 2, &rules));
+    assert_eq!(Ok(1), sum_after_blinks(vec![1], 1, &rules));
+}
+
+#[test]
+fn test_repeated_stones() {
+    // every copy of a starting stone counts
+    assert_eq!(Ok(2 * 7 + 15), sum_after_blinks(vec![125, 17, 125], 6, &PUZZLE_RULES));
+    assert_eq!(Ok(3), sum_after_blinks(vec![0, 0, 0], 1, &PUZZLE_RULES));
+    assert_eq!(Some(&3), stone_distribution(&[0, 0, 0], 1, &PUZZLE_RULES).unwrap().get(&1));
+}
+
+#[test]
+fn test_distribution() {
+    // 2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2
+    let distribution: HashMap<usize, usize> = stone_distribution(&[125, 17], 6, &PUZZLE_RULES).unwrap();
+    assert_eq!(22, distribution.values().sum::<usize>());
+    assert_eq!(4, distribution[&2]);
+    assert_eq!(2, distribution[&40]);
+    assert_eq!(1, distribution[&2097446912]);
+    assert_eq!(15, distribution.len());
+
+    // from 0 the stones only ever take 54 values
+    let stones: Vec<usize> = closed_stone_set(&[0], &PUZZLE_RULES, 1000).unwrap();
+    assert_eq!(54, stones.len());
+    assert!(closed_stone_set(&[0], &PUZZLE_RULES, 50).is_none());
+    // doubling grows without end
+    let doubling: Vec<StoneRule> = parse_rules("any -> multiply 2").unwrap();
+    assert!(closed_stone_set(&[1], &doubling, 1000).is_none());
+
+    let matrix: TransitionMatrix = transition_matrix(&stones, &PUZZLE_RULES);
+    let i_2024: usize = stones.binary_search(&2024).unwrap();
+    assert_eq!(vec![20, 24], matrix.children[i_2024].iter().map(|&i| stones[i]).collect::<Vec<_>>());
+    let i_1: usize = stones.binary_search(&1).unwrap();
+    assert_eq!(vec![i_2024], matrix.children[i_1]);
+
+    // exact while the counts stay below the modulus
+    let modulus: u64 = 1_000_000_007;
+    let exact: HashMap<usize, usize> = stone_distribution(&[125, 17], 25, &PUZZLE_RULES).unwrap();
+    let modular: HashMap<usize, u64> = distribution_mod(&[125, 17], 25, &PUZZLE_RULES, modulus, MAX_CLOSED_VALUES).unwrap();
+    assert_eq!(exact.len(), modular.len());
+    assert!(exact.iter().all(|(stone, &count)| modular[stone] == count as u64));
+
+    let total = |distribution: &HashMap<usize, u64>| distribution.values().fold(0, |total, &count| (total + count) % modulus);
+    let modular: HashMap<usize, u64> = distribution_mod(&[125, 17], 75, &PUZZLE_RULES, modulus, MAX_CLOSED_VALUES).unwrap();
+    assert_eq!(65601038650482 % modulus, total(&modular));
+
+    // a huge number of blinks: 0 -> 1 -> 2024 -> 20 24 -> 2 0 2 4, so that blinks + 4 blinks from
+    // a single 0 are the same as blinks blinks from the four stones
+    let blinks: u64 = 1_000_000_000_000;
+    let from_zero: HashMap<usize, u64> = distribution_mod(&[0], blinks + 4, &PUZZLE_RULES, modulus, MAX_CLOSED_VALUES).unwrap();
+    let from_four: HashMap<usize, u64> = distribution_mod(&[2, 0, 2, 4], blinks, &PUZZLE_RULES, modulus, MAX_CLOSED_VALUES).unwrap();
+    assert_eq!(from_zero, from_four);
+
+    // blinking one at a time gives the same counts
+    assert_eq!(modular, stone_distribution_mod(&[125, 17], 75, &PUZZLE_RULES, modulus).unwrap());
+    assert_eq!(Ok(HashMap::from([(1, 1)])), stone_distribution_mod(&[0, 0, 0], 1, &PUZZLE_RULES, 2));
+
+    // real puzzle inputs have closed sets of thousands of values; the counts here follow a
+    // recurrence of 1382 terms, so 1400 blinks already take the polynomial power
+    let rocks: [usize; 8] = [0, 7, 198844, 5687836, 58, 2, 784, 4];
+    assert_eq!(3916, closed_stone_set(&rocks, &PUZZLE_RULES, MAX_CLOSED_VALUES).unwrap().len());
+    let modular: HashMap<usize, u64> = distribution_mod(&rocks, 1400, &PUZZLE_RULES, modulus, MAX_CLOSED_VALUES).unwrap();
+    assert_eq!(stone_distribution_mod(&rocks, 1400, &PUZZLE_RULES, modulus).unwrap(), modular);
+    // a modulus which is not prime works as well
+    let modular: HashMap<usize, u64> = distribution_mod(&[125, 17], 75, &PUZZLE_RULES, 1 << 40, MAX_CLOSED_VALUES).unwrap();
+    assert_eq!(65601038650482 % (1 << 40), modular.values().fold(0, |total, &count| (total + count) % (1 << 40)));
+
+    // exact counts run out of room somewhere before 200 blinks
+    assert_eq!(Err(StoneError::CountOverflow), sum_after_blinks(vec![125, 17], 200, &PUZZLE_RULES));
+}
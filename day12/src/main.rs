@@ -1,5 +1,6 @@
 use std::fs;
-use std::collections::{BTreeSet, BTreeMap};
+use std::env;
+use std::collections::{BTreeSet, BTreeMap, HashMap};
 use std::time::Instant;
 
 static STEPS: [Direction; 4] = [Direction::UP, Direction::RIGHT, Direction::DOWN, Direction::LEFT];
@@ -180,6 +181,226 @@ fn get_same_adjacent_plots(plot: &Plot, map_bounds: &[usize; 2],
     next_plots
 }
 
+struct Garden {
+    num_rows: usize,
+    num_cols: usize,
+    plots: Vec<char>,
+}
+
+fn parse_garden(textdata: &str) -> Garden {
+    let num_cols: usize = textdata.lines().next().map_or(0, |line| line.chars().count());
+    let plots: Vec<char> = textdata.lines().flat_map(|line| line.chars()).collect();
+    assert!(num_cols > 0 && textdata.lines().all(|line| line.chars().count() == num_cols), "Garden rows differ in length");
+    Garden { num_rows: plots.len() / num_cols, num_cols, plots }
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        DisjointSet { parent: (0..size).collect(), rank: vec![0; size] }
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn union(&mut self, node1: usize, node2: usize) {
+        let (root1, root2) = (self.find(node1), self.find(node2));
+        if root1 == root2 { return }
+        let (low, high) = if self.rank[root1] < self.rank[root2] { (root1, root2) } else { (root2, root1) };
+        self.parent[low] = high;
+        if self.rank[low] == self.rank[high] { self.rank[high] += 1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Region {
+    id: usize,
+    chr: char,
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+    bbox: [usize; 4],  // min row, min col, max row, max col; inclusive
+}
+
+// region id of every plot, ids numbered in reading order of the first plot of each region
+fn label_plots(garden: &Garden) -> Vec<usize> {
+    let Garden { num_rows, num_cols, plots } = garden;
+    let mut regions = DisjointSet::new(plots.len());
+    for index in 0..plots.len() {
+        if (index + 1) % num_cols != 0 && plots[index] == plots[index + 1] { regions.union(index, index + 1) }
+        if index + num_cols < num_rows * num_cols && plots[index] == plots[index + num_cols] { regions.union(index, index + num_cols) }
+    }
+
+    let mut root_labels: Vec<Option<usize>> = vec![None; plots.len()];
+    let mut num_labels: usize = 0;
+    (0..plots.len())
+        .map(|index| {
+            let root: usize = regions.find(index);
+            *root_labels[root].get_or_insert_with(|| {
+                num_labels += 1;
+                num_labels - 1
+            })
+        })
+        .collect()
+}
+
+// a region has as many sides as corners. Looking at each plot's four corners: it is an outer corner
+// when neither neighbour along the corner is in the region, and an inner one when both are but the
+// diagonal plot is not. Comparing labels instead of plants keeps regions which only touch
+// diagonally apart
+fn measure_regions(garden: &Garden, labels: &[usize]) -> Vec<Region> {
+    let Garden { num_rows, num_cols, plots } = garden;
+    let num_regions: usize = labels.iter().max().map_or(0, |&label| label + 1);
+    let mut regions: Vec<Region> = (0..num_regions)
+        .map(|id| Region { id, chr: ' ', area: 0, perimeter: 0, sides: 0, bbox: [usize::MAX, usize::MAX, 0, 0] })
+        .collect();
+
+    let label_at = |row: isize, col: isize| -> Option<usize> {
+        if row < 0 || col < 0 || row >= *num_rows as isize || col >= *num_cols as isize { return None }
+        Some(labels[row as usize * num_cols + col as usize])
+    };
+    for (index, &label) in labels.iter().enumerate() {
+        let [row, col] = [index / num_cols, index % num_cols];
+        let region: &mut Region = &mut regions[label];
+        region.chr = plots[index];
+        region.area += 1;
+        region.bbox = [region.bbox[0].min(row), region.bbox[1].min(col), region.bbox[2].max(row), region.bbox[3].max(col)];
+
+        let [row, col] = [row as isize, col as isize];
+        let same: [bool; 4] = STEPS.map(|direction| {
+            let [row_step, col_step] = direction.step();
+            label_at(row + row_step, col + col_step) == Some(label)
+        });
+        region.perimeter += same.iter().filter(|&&is_same| !is_same).count();
+        for (i, direction) in STEPS.iter().enumerate() {
+            let next: usize = (i + 1) % 4;
+            let [row_step1, col_step1] = direction.step();
+            let [row_step2, col_step2] = STEPS[next].step();
+            let diagonal_same: bool = label_at(row + row_step1 + row_step2, col + col_step1 + col_step2) == Some(label);
+            let is_outer_corner: bool = !same[i] && !same[next];
+            let is_inner_corner: bool = same[i] && same[next] && !diagonal_same;
+            if is_outer_corner || is_inner_corner { region.sides += 1 }
+        }
+    }
+    regions
+}
+
+// a closed fence line; points are plot corners as [row, col], one per change of direction.
+// Outer boundaries run clockwise on the map, holes anticlockwise
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Boundary {
+    region: usize,
+    is_hole: bool,
+    points: Vec<[usize; 2]>,
+}
+
+// every fence segment is directed so that its region is on its right, and segments are chained
+// at the plot corners. Where a region touches itself diagonally two chains meet at one corner;
+// turning left there (away from the region) keeps the two pieces of outside apart, so plots
+// enclosed by a region, even when they touch at a corner like in the 6x6 garden, each get their
+// own hole
+fn region_boundaries(garden: &Garden, labels: &[usize]) -> Vec<Boundary> {
+    let Garden { num_rows, num_cols, .. } = garden;
+    let same = |row: isize, col: isize, label: usize| -> bool {
+        row >= 0 && col >= 0 && row < *num_rows as isize && col < *num_cols as isize
+            && labels[row as usize * num_cols + col as usize] == label
+    };
+
+    // (region, start corner, direction of travel); the side facing STEPS[i] is travelled
+    // along STEPS[i+1]
+    let corner_offsets: [[usize; 2]; 4] = [[0, 0], [0, 1], [1, 1], [1, 0]];
+    let mut segments: Vec<(usize, [usize; 2], usize)> = Vec::new();
+    for (index, &label) in labels.iter().enumerate() {
+        let [row, col] = [index / num_cols, index % num_cols];
+        for (i, direction) in STEPS.iter().enumerate() {
+            let [row_step, col_step] = direction.step();
+            if same(row as isize + row_step, col as isize + col_step, label) { continue }
+            let [row_offset, col_offset] = corner_offsets[i];
+            segments.push((label, [row + row_offset, col + col_offset], (i + 1) % 4));
+        }
+    }
+    let mut outgoing: HashMap<(usize, [usize; 2]), Vec<usize>> = HashMap::new();
+    for (i, &(label, start, _)) in segments.iter().enumerate() {
+        outgoing.entry((label, start)).or_default().push(i);
+    }
+
+    let mut used: Vec<bool> = vec![false; segments.len()];
+    let mut boundaries: Vec<Boundary> = Vec::new();
+    for first in 0..segments.len() {
+        if used[first] { continue }
+        let mut chain: Vec<usize> = Vec::new();
+        let mut current: usize = first;
+        while !used[current] {
+            used[current] = true;
+            chain.push(current);
+            let (label, [row, col], direction) = segments[current];
+            let [row_step, col_step] = STEPS[direction].step();
+            let end: [usize; 2] = [(row as isize + row_step) as usize, (col as isize + col_step) as usize];
+            let options: &Vec<usize> = &outgoing[&(label, end)];
+            current = [(direction + 3) % 4, direction, (direction + 1) % 4].iter()
+                .find_map(|&turn| options.iter().find(|&&next| segments[next].2 == turn))
+                .copied()
+                .unwrap();
+        }
+
+        let points: Vec<[usize; 2]> = (0..chain.len())
+            .filter(|&i| segments[chain[i]].2 != segments[chain[(i + chain.len() - 1) % chain.len()]].2)
+            .map(|i| segments[chain[i]].1)
+            .collect();
+        let is_hole: bool = signed_double_area(&points) < 0;
+        boundaries.push(Boundary { region: segments[first].0, is_hole, points });
+    }
+    boundaries.sort_by_key(|boundary| (boundary.region, boundary.is_hole));
+    boundaries
+}
+
+// shoelace formula with x along columns and y down the rows, so clockwise on the map is positive
+fn signed_double_area(points: &[[usize; 2]]) -> isize {
+    points.iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(&[row1, col1], &[row2, col2])| col1 as isize * row2 as isize - col2 as isize * row1 as isize)
+        .sum()
+}
+
+fn plant_colour(chr: char) -> String {
+    format!("hsl({}, 65%, 60%)", (chr as u32 * 47) % 360)
+}
+
+// one path per region, holes included as extra subpaths which the nonzero fill rule leaves out
+// thanks to their opposite direction
+fn garden_svg(garden: &Garden, regions: &[Region], boundaries: &[Boundary], scale: usize) -> String {
+    let mut svg: String = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        garden.num_cols * scale, garden.num_rows * scale,
+    );
+    for region in regions {
+        let path: Vec<String> = boundaries.iter()
+            .filter(|boundary| boundary.region == region.id)
+            .map(|boundary| {
+                let points: Vec<String> = boundary.points.iter()
+                    .map(|[row, col]| format!("{} {}", col * scale, row * scale))
+                    .collect();
+                format!("M {} Z", points.join(" L "))
+            })
+            .collect();
+        svg += &format!(
+            "  <path d=\"{}\" fill=\"{}\" stroke=\"black\"><title>{} ({})</title></path>\n",
+            path.join(" "), plant_colour(region.chr), region.chr, region.id,
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
 fn main() {
     let filename: &str = "input.txt";
     let textdata: String = fs::read_to_string(filename)
@@ -187,6 +408,26 @@ fn main() {
 
     let before = Instant::now();
 
+    let args: Vec<String> = env::args().collect();
+    if !args.iter().any(|arg| arg == "--bfs") {
+        let garden: Garden = parse_garden(&textdata);
+        let labels: Vec<usize> = label_plots(&garden);
+        let regions: Vec<Region> = measure_regions(&garden, &labels);
+        if let Some(i_svg) = args.iter().position(|arg| arg == "--svg") {
+            let svg_filename: &str = args.get(i_svg+1).expect("--svg needs a file name");
+            let svg: String = garden_svg(&garden, &regions, &region_boundaries(&garden, &labels), 10);
+            fs::write(svg_filename, svg).unwrap_or_else(|_| panic!("Cannot write file {svg_filename}"));
+        }
+        let total_price: usize = regions.iter().map(|region| region.area * region.perimeter).sum();
+        let discount_price: usize = regions.iter().map(|region| region.area * region.sides).sum();
+
+        let after = before.elapsed();
+        println!("(Part  I) total price: {total_price}");
+        println!("(Part II) total price: {discount_price}");
+        println!("(Parts I & II) elapsed time: {after:.2?}");
+        return
+    }
+
     // Put the entire data into a hashmap with locations and letters
     let chr_map: Vec<Vec<char>> = textdata
         .lines()
@@ -382,3 +623,90 @@ fn straight_fences_6x6() {
         .sum();
     assert_eq!(368, total_price);
 }
+
+#[test]
+fn union_find_regions() {
+    let prices = |filename: &str| -> [usize; 2] {
+        let textdata: String = fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+        let garden: Garden = parse_garden(&textdata);
+        let regions: Vec<Region> = measure_regions(&garden, &label_plots(&garden));
+        [
+            regions.iter().map(|region| region.area * region.perimeter).sum(),
+            regions.iter().map(|region| region.area * region.sides).sum(),
+        ]
+    };
+    assert_eq!([1930, 1206], prices("test_input.txt"));
+    assert_eq!(236, prices("test_input_5x5.txt")[1]);
+    assert_eq!(368, prices("test_input_6x6.txt")[1]);
+
+    // the two B regions of the 6x6 garden only touch at a corner
+    let textdata: String = fs::read_to_string("test_input_6x6.txt").unwrap();
+    let garden: Garden = parse_garden(&textdata);
+    let regions: Vec<Region> = measure_regions(&garden, &label_plots(&garden));
+    assert_eq!(vec![
+        Region { id: 0, chr: 'A', area: 28, perimeter: 40, sides: 12, bbox: [0, 0, 5, 5] },
+        Region { id: 1, chr: 'B', area: 4, perimeter: 8, sides: 4, bbox: [1, 3, 2, 4] },
+        Region { id: 2, chr: 'B', area: 4, perimeter: 8, sides: 4, bbox: [3, 1, 4, 2] },
+    ], regions);
+
+    let garden: Garden = parse_garden("AAAA\nBBCD\nBBCC\nEEEC\n");
+    let labels: Vec<usize> = label_plots(&garden);
+    assert_eq!(vec![0, 0, 0, 0, 1, 1, 2, 3, 1, 1, 2, 2, 4, 4, 4, 2], labels);
+    let regions: Vec<Region> = measure_regions(&garden, &labels);
+    assert_eq!([4, 4, 4, 1, 3], regions.iter().map(|region| region.area).collect::<Vec<_>>()[..]);
+    assert_eq!([4, 4, 8, 4, 4], regions.iter().map(|region| region.sides).collect::<Vec<_>>()[..]);
+    assert_eq!(140, regions.iter().map(|region| region.area * region.perimeter).sum::<usize>());
+    assert_eq!(80, regions.iter().map(|region| region.area * region.sides).sum::<usize>());
+}
+
+#[test]
+#[should_panic(expected = "Garden rows differ in length")]
+fn ragged_garden() {
+    // 6 plots would split evenly into rows of 2, but the rows are 2, 3 and 1 long
+    parse_garden("AB\nABC\nA\n");
+}
+
+#[test]
+fn fence_outlines() {
+    for filename in ["test_input.txt", "test_input_5x5.txt", "test_input_6x6.txt"] {
+        let textdata: String = fs::read_to_string(filename).unwrap();
+        let garden: Garden = parse_garden(&textdata);
+        let labels: Vec<usize> = label_plots(&garden);
+        let regions: Vec<Region> = measure_regions(&garden, &labels);
+        let boundaries: Vec<Boundary> = region_boundaries(&garden, &labels);
+        for region in regions.iter() {
+            let outlines: Vec<&Boundary> = boundaries.iter().filter(|boundary| boundary.region == region.id).collect();
+            assert_eq!(1, outlines.iter().filter(|boundary| !boundary.is_hole).count(), "{filename}");
+            // a corner on the outline for every side, and the holes taken out of the area
+            assert_eq!(region.sides, outlines.iter().map(|boundary| boundary.points.len()).sum::<usize>(), "{filename}");
+            assert_eq!(2 * region.area as isize, outlines.iter().map(|boundary| signed_double_area(&boundary.points)).sum::<isize>(), "{filename}");
+        }
+    }
+
+    // the A region has a separate square hole for each B region, even where those touch
+    let textdata: String = fs::read_to_string("test_input_6x6.txt").unwrap();
+    let garden: Garden = parse_garden(&textdata);
+    let boundaries: Vec<Boundary> = region_boundaries(&garden, &label_plots(&garden));
+    assert_eq!(vec![
+        Boundary { region: 0, is_hole: false, points: vec![[0, 0], [0, 6], [6, 6], [6, 0]] },
+        Boundary { region: 0, is_hole: true, points: vec![[1, 3], [3, 3], [3, 5], [1, 5]] },
+        Boundary { region: 0, is_hole: true, points: vec![[3, 1], [5, 1], [5, 3], [3, 3]] },
+        Boundary { region: 1, is_hole: false, points: vec![[1, 3], [1, 5], [3, 5], [3, 3]] },
+        Boundary { region: 2, is_hole: false, points: vec![[3, 1], [3, 3], [5, 3], [5, 1]] },
+    ], boundaries);
+
+    // nested regions: C inside B inside A
+    let garden: Garden = parse_garden("AAAAA\nABBBA\nABCBA\nABBBA\nAAAAA\n");
+    let labels: Vec<usize> = label_plots(&garden);
+    let boundaries: Vec<Boundary> = region_boundaries(&garden, &labels);
+    assert_eq!([(0, false), (0, true), (1, false), (1, true), (2, false)], boundaries.iter().map(|boundary| (boundary.region, boundary.is_hole)).collect::<Vec<_>>()[..]);
+    assert_eq!([50, -18, 18, -2, 2], boundaries.iter().map(|boundary| signed_double_area(&boundary.points)).collect::<Vec<_>>()[..]);
+
+    let regions: Vec<Region> = measure_regions(&garden, &labels);
+    let svg: String = garden_svg(&garden, &regions, &boundaries, 10);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"50\" height=\"50\">"));
+    assert_eq!(3, svg.matches("<path").count());
+    assert!(svg.contains("<path d=\"M 0 0 L 50 0 L 50 50 L 0 50 Z M 10 10 L 10 40 L 40 40 L 40 10 Z\""));
+    assert!(svg.contains(&format!("fill=\"{}\"", plant_colour('C'))));
+}
@@ -1,4 +1,6 @@
 use std::fs;
+use std::env;
+use std::io::{self, Read};
 use regex::{Regex, Match};
 use std::time::Instant;
 
@@ -36,7 +38,281 @@ fn standardize_string(string: String) -> String {
     String::from("do()") + string.as_str() + "do()"
 }
 
+const KEYWORDS: [&[u8]; 3] = [b"mul(", b"do()", b"don't()"];
+const MAX_KEYWORD_LEN: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ScanTotals {
+    bytes_scanned: u64,
+    sum_of_muls: i64,
+    sum_of_enabled_muls: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScanState {
+    Keyword([u8; MAX_KEYWORD_LEN], usize),
+    FirstNum(i64, usize),
+    SecondNum(i64, i64, usize),
+}
+
+// byte-at-a-time automaton, so an instruction cut in half by a chunk boundary is simply picked up
+// again with the next chunk; only the partially matched instruction is kept between chunks
+struct MulScanner {
+    state: ScanState,
+    enabled: bool,
+    totals: ScanTotals,
+}
+
+impl MulScanner {
+    fn new() -> Self {
+        MulScanner {
+            state: ScanState::Keyword([0; MAX_KEYWORD_LEN], 0),
+            enabled: true,  // same as prepending "do()" in standardize_string
+            totals: ScanTotals { bytes_scanned: 0, sum_of_muls: 0, sum_of_enabled_muls: 0 },
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) {
+        for &byte in chunk { self.step(byte); }
+        self.totals.bytes_scanned += chunk.len() as u64;
+    }
+
+    fn step(&mut self, byte: u8) {
+        self.state = match self.state {
+            ScanState::Keyword(mut buffer, len) => {
+                buffer[len] = byte;
+                let prefix: &[u8] = &buffer[..len+1];
+                match KEYWORDS.iter().find(|keyword| keyword.starts_with(prefix)) {
+                    Some(&keyword) if keyword.len() == prefix.len() => self.finish_keyword(keyword),
+                    Some(_) => ScanState::Keyword(buffer, len+1),
+                    None if len > 0 => { self.reset(); return self.step(byte) },
+                    None => ScanState::Keyword(buffer, 0),
+                }
+            },
+            ScanState::FirstNum(num, digits) => match byte {
+                b'0'..=b'9' => match push_digit(num, byte) {
+                    Some(num) => ScanState::FirstNum(num, digits+1),
+                    None => { self.reset(); return },
+                },
+                b',' if digits > 0 => ScanState::SecondNum(num, 0, 0),
+                _ => { self.reset(); return self.step(byte) },
+            },
+            ScanState::SecondNum(num1, num2, digits) => match byte {
+                b'0'..=b'9' => match push_digit(num2, byte) {
+                    Some(num2) => ScanState::SecondNum(num1, num2, digits+1),
+                    None => { self.reset(); return },
+                },
+                b')' if digits > 0 => {
+                    self.add_mul(num1, num2);
+                    ScanState::Keyword([0; MAX_KEYWORD_LEN], 0)
+                },
+                _ => { self.reset(); return self.step(byte) },
+            },
+        };
+    }
+
+    fn finish_keyword(&mut self, keyword: &[u8]) -> ScanState {
+        match keyword {
+            b"mul(" => return ScanState::FirstNum(0, 0),
+            b"do()" => self.enabled = true,
+            _ => self.enabled = false,
+        }
+        ScanState::Keyword([0; MAX_KEYWORD_LEN], 0)
+    }
+
+    fn add_mul(&mut self, num1: i64, num2: i64) {
+        let product: i64 = num1 * num2;
+        self.totals.sum_of_muls += product;
+        if self.enabled { self.totals.sum_of_enabled_muls += product }
+    }
+
+    fn reset(&mut self) {
+        self.state = ScanState::Keyword([0; MAX_KEYWORD_LEN], 0);
+    }
+}
+
+// operands are capped so that a product always fits in an i64
+fn push_digit(num: i64, byte: u8) -> Option<i64> {
+    let num: i64 = num * 10 + (byte - b'0') as i64;
+    if num < 1 << 31 { Some(num) } else { None }
+}
+
+// yields the running totals after every chunk, memory use does not depend on the input length
+struct StreamScanner<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    scanner: MulScanner,
+}
+
+impl<R: Read> StreamScanner<R> {
+    fn new(reader: R, chunk_size: usize) -> Self {
+        StreamScanner { reader, buffer: vec![0; chunk_size], scanner: MulScanner::new() }
+    }
+}
+
+impl<R: Read> Iterator for StreamScanner<R> {
+    type Item = io::Result<ScanTotals>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(num_read) => {
+                    self.scanner.feed(&self.buffer[..num_read]);
+                    return Some(Ok(self.scanner.totals));
+                },
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+fn scan_stdin(chunk_size: usize) {
+    let before = Instant::now();
+
+    let stream = StreamScanner::new(io::stdin().lock(), chunk_size);
+    let mut totals = ScanTotals { bytes_scanned: 0, sum_of_muls: 0, sum_of_enabled_muls: 0 };
+    for result in stream {
+        totals = result.expect("Cannot read from stdin");
+        let ScanTotals { bytes_scanned, sum_of_muls, sum_of_enabled_muls } = totals;
+        println!("{bytes_scanned} bytes: (Part I) {sum_of_muls}, (Part II) {sum_of_enabled_muls}");
+    }
+
+    let after = before.elapsed();
+    println!("Time elapsed (stream): {after:2?}");
+    println!("(Part  I): Sum of mul pairs: {}", totals.sum_of_muls);
+    println!("(Part II): Sum of filtered mul pairs: {}", totals.sum_of_enabled_muls);
+}
+
+const RESET: &str = "\x1b[0m";
+const STYLE_ENABLED: &str = "";
+const STYLE_DISABLED: &str = "\x1b[90m";
+const STYLE_DO: &str = "\x1b[1;36m";
+const STYLE_DONT: &str = "\x1b[1;35m";
+const STYLE_COUNTED: &str = "\x1b[1;32m";
+const STYLE_SKIPPED: &str = "\x1b[9;32m";
+const STYLE_NEAR_MISS: &str = "\x1b[4;33m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Do,
+    Dont,
+    Mul(i32, i32),
+    NearMiss,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LedgerEntry {
+    start: usize,
+    text: String,
+    instruction: Instruction,
+    enabled: bool,
+}
+
+impl LedgerEntry {
+    fn contribution(&self) -> i32 {
+        match self.instruction {
+            Instruction::Mul(val1, val2) if self.enabled => val1 * val2,
+            _ => 0,
+        }
+    }
+}
+
+// near misses are tried last, so a valid mul(...) is never reported as one
+fn string_to_instructions(string: &str) -> Vec<LedgerEntry> {
+    let re_pattern: Regex = Regex::new(concat!(
+        "(?<do>do\\(\\))|(?<dont>don't\\(\\))|mul\\((?<val1>[0-9]+),(?<val2>[0-9]+)\\)",
+        "|(?<miss>mul\\s*[(\\[{<]\\s*[0-9]+\\s*,\\s*[0-9]+\\s*[)\\]}>])",
+    )).unwrap();
+
+    let mut enabled: bool = true;
+    re_pattern.captures_iter(string)
+        .map(|caps| {
+            let found: Match = caps.get(0).unwrap();
+            let instruction: Instruction = if caps.name("do").is_some() {
+                Instruction::Do
+            } else if caps.name("dont").is_some() {
+                Instruction::Dont
+            } else if caps.name("miss").is_some() {
+                Instruction::NearMiss
+            } else {
+                Instruction::Mul(caps["val1"].parse().unwrap(), caps["val2"].parse().unwrap())
+            };
+            match instruction {
+                Instruction::Do => enabled = true,
+                Instruction::Dont => enabled = false,
+                _ => (),
+            }
+            LedgerEntry { start: found.start(), text: found.as_str().to_owned(), instruction, enabled }
+        })
+        .collect()
+}
+
+fn explain(string: &str) -> (String, Vec<LedgerEntry>) {
+    let ledger: Vec<LedgerEntry> = string_to_instructions(string);
+
+    let mut annotated: String = String::new();
+    let mut cursor: usize = 0;
+    let mut enabled: bool = true;
+    for entry in ledger.iter() {
+        let region_style: &str = if enabled { STYLE_ENABLED } else { STYLE_DISABLED };
+        annotated += &format!("{region_style}{}{RESET}", &string[cursor..entry.start]);
+        let style: &str = match entry.instruction {
+            Instruction::Do => STYLE_DO,
+            Instruction::Dont => STYLE_DONT,
+            Instruction::Mul(_, _) if entry.enabled => STYLE_COUNTED,
+            Instruction::Mul(_, _) => STYLE_SKIPPED,
+            Instruction::NearMiss => STYLE_NEAR_MISS,
+        };
+        annotated += &format!("{style}{}{RESET}", entry.text);
+        cursor = entry.start + entry.text.len();
+        enabled = entry.enabled;
+    }
+    let region_style: &str = if enabled { STYLE_ENABLED } else { STYLE_DISABLED };
+    annotated += &format!("{region_style}{}{RESET}", &string[cursor..]);
+
+    (annotated, ledger)
+}
+
+fn print_explanation(string: &str) {
+    let (annotated, ledger) = explain(string);
+    println!("{annotated}");
+    println!();
+
+    let mut running_total: i32 = 0;
+    for entry in ledger.iter() {
+        let LedgerEntry { start, text, .. } = entry;
+        let note: String = match entry.instruction {
+            Instruction::Do => String::from("enable"),
+            Instruction::Dont => String::from("disable"),
+            Instruction::NearMiss => String::from("malformed, ignored"),
+            Instruction::Mul(val1, val2) if entry.enabled => {
+                running_total += entry.contribution();
+                format!("{val1} * {val2} = {}, total {running_total}", entry.contribution())
+            },
+            Instruction::Mul(_, _) => String::from("disabled, skipped"),
+        };
+        println!("{start:>8}  {text:<16} {note}");
+    }
+    println!("(Part II): Sum of filtered mul pairs: {running_total}");
+}
+
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--stream") {
+        let chunk_size: usize = args.get(2).map_or(1 << 16, |arg| arg.parse().expect("Chunk size must be a positive integer"));
+        assert!(chunk_size > 0, "Chunk size must be a positive integer");
+        scan_stdin(chunk_size);
+        return
+    }
+    if args.get(1).map(String::as_str) == Some("--explain") {
+        let filename: &str = args.get(2).map_or("input.txt", String::as_str);
+        let filedata: String = fs::read_to_string(filename).expect("Cannot open file {filename}");
+        print_explanation(&filedata);
+        return
+    }
+
     let filename: &str = "input.txt";
     let filedata: String = fs::read_to_string(filename).expect("Cannot open file {filename}");
 
@@ -111,3 +387,42 @@ fn test_do_dont() {
     assert_eq!(vec![[0 + shift, 13 + shift],], do_ranges);
 
 }
+
+#[test]
+fn test_stream_scanner() {
+    let test_string: &str = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    // every chunk size puts the boundaries in a different place, including inside "don't()"
+    for chunk_size in 1..=test_string.len() {
+        let totals: ScanTotals = StreamScanner::new(test_string.as_bytes(), chunk_size)
+            .map(|result| result.unwrap())
+            .last()
+            .unwrap();
+        assert_eq!(161, totals.sum_of_muls);
+        assert_eq!(48, totals.sum_of_enabled_muls);
+        assert_eq!(test_string.len() as u64, totals.bytes_scanned);
+    }
+
+    let mut scanner = MulScanner::new();
+    scanner.feed(b"mumul(1,2)mul(3,4mul(5,6)do(don't()mul(1000,1)");
+    assert_eq!(2 + 30 + 1000, scanner.totals.sum_of_muls);
+    assert_eq!(2 + 30, scanner.totals.sum_of_enabled_muls);
+}
+
+#[test]
+fn test_explain() {
+    let test_string: &str = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+    let (annotated, ledger) = explain(test_string);
+
+    let texts: Vec<&str> = ledger.iter().map(|entry| entry.text.as_str()).collect();
+    assert_eq!(vec!["mul(2,4)", "mul[3,7]", "don't()", "mul(5,5)", "mul(32,64]", "mul(11,8)", "do()", "mul(8,5)"], texts);
+    assert_eq!(Instruction::NearMiss, ledger[1].instruction);
+    assert_eq!(Instruction::NearMiss, ledger[4].instruction);
+
+    let contributions: Vec<i32> = ledger.iter().map(|entry| entry.contribution()).collect();
+    assert_eq!(vec![8, 0, 0, 0, 0, 0, 0, 40], contributions);
+    assert_eq!(48, contributions.iter().sum::<i32>());
+
+    let stripped: String = Regex::new("\\x1b\\[[0-9;]*m").unwrap().replace_all(&annotated, "").into_owned();
+    assert_eq!(test_string, stripped);
+}
@@ -1,23 +1,24 @@
 use std::fs;
+use std::env;
+use std::collections::HashMap;
 use std::time::Instant;
 
 const XMAS_ARRAY: [char; 4] = ['X', 'M', 'A', 'S'];
 const XMAS_LEN: usize = XMAS_ARRAY.len();
+const COOR_STEPS: [[i32; 2]; 8] = [
+    [0, 1], [1, 1], [1, 0], [1, -1], [0, -1], [-1, -1], [-1, 0], [-1, 1]
+];
 
 fn find_all_matches(word_search: &Vec<Vec<char>>) -> usize {
     let nrows: usize = word_search.len();
     let ncols: usize = word_search[0].len();
     word_search.iter().for_each(|vec| assert_eq!(vec.len(), ncols));
 
-    let coor_steps: [[i32; 2]; 8] = [
-        [0, 1], [1, 1], [1, 0], [1, -1], [0, -1], [-1, -1], [-1, 0], [-1, 1]
-    ];
-
     let coor_with_x = (0..nrows).flat_map(|i| (0..ncols).map(move |j| (i, j)))
         .filter(|&(row, col)| word_search[row][col]=='X');
 
     let bounded_cases = coor_with_x
-        .flat_map(|(row, col)| coor_steps.iter()
+        .flat_map(|(row, col)| COOR_STEPS.iter()
             .map(move |&[inc_row, inc_col]| (row, col, inc_row, inc_col))
         )
         .filter(|&(row, _, inc_row, _)| row as i32 + (XMAS_LEN-1) as i32 * inc_row >= 0i32)
@@ -28,8 +29,8 @@ fn find_all_matches(word_search: &Vec<Vec<char>>) -> usize {
      let num_matches: usize = bounded_cases
          .filter(|&(row, col, step_row, step_col)| {
              XMAS_ARRAY.iter().enumerate().all(|(i, &chr)| {
-                 let row_i = row+i * step_row as usize;
-                 let col_i = col+i * step_col as usize;
+                 let row_i = (row as i32 + i as i32 * step_row) as usize;
+                 let col_i = (col as i32 + i as i32 * step_col) as usize;
                  word_search[row_i][col_i] == chr
              })
          })
@@ -39,33 +40,244 @@ fn find_all_matches(word_search: &Vec<Vec<char>>) -> usize {
 }
 
 fn find_xmas(word_search: Vec<Vec<char>>) -> usize {
+    let templates: Vec<Template> = parse_templates(XMAS_TEMPLATE);
+    match_templates(&word_search, &templates, SymmetryMode::Rotations).len()
+}
+
+const XMAS_TEMPLATE: &str = "M.S\n.A.\nM.S";
+const WILDCARD: char = '.';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Template {
+    cells: Vec<Vec<Option<char>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SymmetryMode {
+    Identity,
+    Rotations,
+    RotationsAndReflections,
+}
+
+// quarter turns clockwise, applied after the (optional) left-right reflection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Symmetry {
+    reflected: bool,
+    quarter_turns: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TemplateMatch {
+    template_index: usize,
+    top_left: [usize; 2],
+    symmetry: Symmetry,
+}
+
+impl Template {
+    fn rotated(&self) -> Template {
+        let nrows: usize = self.cells.len();
+        let ncols: usize = self.cells[0].len();
+        let cells = (0..ncols)
+            .map(|row| (0..nrows).map(|col| self.cells[nrows-1-col][row]).collect())
+            .collect();
+        Template { cells }
+    }
+
+    fn reflected(&self) -> Template {
+        let cells = self.cells.iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect();
+        Template { cells }
+    }
+
+    // variants which look the same (e.g. rotations of a symmetric template) are only kept once, so
+    // each location is counted at most once per template
+    fn variants(&self, mode: SymmetryMode) -> Vec<(Symmetry, Template)> {
+        let reflections: &[bool] = match mode {
+            SymmetryMode::RotationsAndReflections => &[false, true],
+            _ => &[false],
+        };
+        let num_turns: u8 = if mode == SymmetryMode::Identity { 1 } else { 4 };
+
+        let mut variants: Vec<(Symmetry, Template)> = Vec::new();
+        for &reflected in reflections {
+            let mut template: Template = if reflected { self.reflected() } else { self.clone() };
+            for quarter_turns in 0..num_turns {
+                if variants.iter().all(|(_, variant)| *variant != template) {
+                    variants.push((Symmetry { reflected, quarter_turns }, template.clone()));
+                }
+                template = template.rotated();
+            }
+        }
+        variants
+    }
+
+    fn matches_at(&self, word_search: &[Vec<char>], row: usize, col: usize) -> bool {
+        self.cells.iter().enumerate().all(|(i, template_row)| {
+            template_row.iter().enumerate().all(|(j, &cell)| match cell {
+                Some(chr) => word_search[row+i][col+j] == chr,
+                None => true,
+            })
+        })
+    }
+}
+
+// templates are separated by blank lines, '.' matches any character
+fn parse_templates(text: &str) -> Vec<Template> {
+    text.split("\n\n")
+        .map(|block| block.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<&str>>())
+        .filter(|lines| !lines.is_empty())
+        .map(|lines| {
+            let cells: Vec<Vec<Option<char>>> = lines.iter()
+                .map(|line| line.trim().chars().map(|chr| (chr != WILDCARD).then_some(chr)).collect())
+                .collect();
+            let ncols: usize = cells[0].len();
+            cells.iter().for_each(|row| assert_eq!(row.len(), ncols, "Template rows must be of equal length"));
+            Template { cells }
+        })
+        .collect()
+}
+
+fn match_templates(word_search: &[Vec<char>], templates: &[Template], mode: SymmetryMode) -> Vec<TemplateMatch> {
     let nrows: usize = word_search.len();
-    let ncols = word_search[0].len();
+    let ncols: usize = word_search[0].len();
     word_search.iter().for_each(|vec| assert_eq!(vec.len(), ncols));
 
-    let mut coors: [[usize; 2]; 4] = [[0, 0],[0, 0],[0, 0],[0, 0],];
-    let mut test_array: [char; 4] = ['A','A','A','A'];
-
-    let product_range = (1..(nrows-1))
-        .flat_map(|i| (1..(ncols-1)).map(move |j| (i, j))) ;
-
-    let coor_with_a = product_range
-        .filter(|&(row, col)| word_search[row][col] == 'A');
-
-    let mut num_matches: usize = 0;
-    for (row, col) in coor_with_a {
-        coors[0] = [row-1, col-1];  // code isn't cleaner with a for-loop
-        coors[1] = [row-1, col+1];
-        coors[2] = [row+1, col-1];
-        coors[3] = [row+1, col+1];
-        for (i, &[row, col]) in coors.iter().enumerate() { test_array[i] = word_search[row][col]; }
-        let num_m: usize = test_array.iter().filter(|&&chr| chr == 'M').count();
-        let num_s: usize = test_array.iter().filter(|&&chr| chr == 'S').count();
-        let opposing = word_search[row-1][col-1] != word_search[row+1][col+1];
-        if num_m == 2 && num_s == 2 && opposing { num_matches += 1 }
+    let mut matches: Vec<TemplateMatch> = Vec::new();
+    for (template_index, template) in templates.iter().enumerate() {
+        for (symmetry, variant) in template.variants(mode) {
+            let height: usize = variant.cells.len();
+            let width: usize = variant.cells[0].len();
+            if height > nrows || width > ncols { continue }
+
+            let product_range = (0..=(nrows-height))
+                .flat_map(|i| (0..=(ncols-width)).map(move |j| (i, j)));
+            matches.extend(product_range
+                .filter(|&(row, col)| variant.matches_at(word_search, row, col))
+                .map(|(row, col)| TemplateMatch { template_index, top_left: [row, col], symmetry })
+            );
+        }
     }
 
-    num_matches
+    matches
+}
+
+fn print_template_matches(word_search: &[Vec<char>], templates: &[Template], mode: SymmetryMode) {
+    let before = Instant::now();
+    let matches: Vec<TemplateMatch> = match_templates(word_search, templates, mode);
+    let after = before.elapsed();
+
+    for TemplateMatch { template_index, top_left: [row, col], symmetry } in matches.iter() {
+        let Symmetry { reflected, quarter_turns } = symmetry;
+        let reflection: &str = if *reflected { "reflected, " } else { "" };
+        println!("template {template_index} at ({row}, {col}) ({reflection}{quarter_turns} quarter turns)");
+    }
+    println!("Time elapsed (templates): {after:2?}");
+    println!("# of matches: {}", matches.len());
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SearchOptions {
+    wrap_around: bool,
+    fold_case: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WordMatch {
+    word: String,
+    start: [usize; 2],
+    direction: [i32; 2],
+}
+
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    word_index: Option<usize>,
+}
+
+// all words share one walk along each ray, so the cost per ray is bounded by the longest word and
+// not by the number of words
+struct Trie {
+    nodes: Vec<TrieNode>,
+    max_depth: usize,
+}
+
+impl Trie {
+    fn new(words: &[&str], fold_case: bool) -> Self {
+        let mut trie = Trie { nodes: vec![TrieNode::default()], max_depth: 0 };
+        for (word_index, word) in words.iter().enumerate() {
+            let mut node: usize = 0;
+            for chr in word.chars().map(|chr| fold(chr, fold_case)) {
+                node = match trie.nodes[node].children.get(&chr) {
+                    Some(&child) => child,
+                    None => {
+                        trie.nodes.push(TrieNode::default());
+                        let child: usize = trie.nodes.len() - 1;
+                        trie.nodes[node].children.insert(chr, child);
+                        child
+                    },
+                };
+            }
+            trie.nodes[node].word_index.get_or_insert(word_index);
+            trie.max_depth = trie.max_depth.max(word.chars().count());
+        }
+        trie
+    }
+}
+
+fn fold(chr: char, fold_case: bool) -> char {
+    if fold_case { chr.to_lowercase().next().unwrap() } else { chr }
+}
+
+fn find_words(word_search: &[Vec<char>], words: &[&str], options: SearchOptions) -> Vec<WordMatch> {
+    let nrows: usize = word_search.len();
+    let ncols: usize = word_search[0].len();
+    word_search.iter().for_each(|vec| assert_eq!(vec.len(), ncols));
+
+    let trie = Trie::new(words, options.fold_case);
+    let mut matches: Vec<WordMatch> = Vec::new();
+    for (row, col) in (0..nrows).flat_map(|i| (0..ncols).map(move |j| (i, j))) {
+        for (i_step, &[step_row, step_col]) in COOR_STEPS.iter().enumerate() {
+            let (mut row_i, mut col_i) = (row as i64, col as i64);
+            let mut node: usize = 0;
+            for _ in 0..trie.max_depth {
+                if options.wrap_around {
+                    row_i = row_i.rem_euclid(nrows as i64);
+                    col_i = col_i.rem_euclid(ncols as i64);
+                } else if row_i < 0 || row_i >= nrows as i64 || col_i < 0 || col_i >= ncols as i64 {
+                    break
+                }
+                let chr: char = fold(word_search[row_i as usize][col_i as usize], options.fold_case);
+                node = match trie.nodes[node].children.get(&chr) {
+                    Some(&child) => child,
+                    None => break,
+                };
+                // a single letter reads the same in every direction, only report it once
+                if let Some(word_index) = trie.nodes[node].word_index {
+                    if words[word_index].chars().count() > 1 || i_step == 0 {
+                        let word: String = words[word_index].to_owned();
+                        matches.push(WordMatch { word, start: [row, col], direction: [step_row, step_col] });
+                    }
+                }
+                row_i += step_row as i64;
+                col_i += step_col as i64;
+            }
+        }
+    }
+
+    matches
+}
+
+fn print_word_matches(word_search: &[Vec<char>], words: &[&str], options: SearchOptions) {
+    let before = Instant::now();
+    let matches: Vec<WordMatch> = find_words(word_search, words, options);
+    let after = before.elapsed();
+
+    for WordMatch { word, start: [row, col], direction: [step_row, step_col] } in matches.iter() {
+        println!("{word} at ({row}, {col}) direction ({step_row}, {step_col})");
+    }
+    println!("Time elapsed (word search): {after:2?}");
+    println!("# of matches: {}", matches.len());
 }
 
 fn main() {
@@ -78,6 +290,33 @@ fn main() {
         .map(|line| line.chars().collect())
         .collect();
 
+    let args: Vec<String> = env::args().collect();
+    if let Some(i_words) = args.iter().position(|arg| arg == "--words") {
+        let words: Vec<&str> = args.get(i_words+1).expect("--words needs a comma-separated list")
+            .split(',')
+            .filter(|word| !word.is_empty())
+            .collect();
+        let options = SearchOptions {
+            wrap_around: args.iter().any(|arg| arg == "--wrap"),
+            fold_case: args.iter().any(|arg| arg == "--fold-case"),
+        };
+        print_word_matches(&word_search, &words, options);
+        return
+    }
+    if let Some(i_templates) = args.iter().position(|arg| arg == "--templates") {
+        let template_file: &str = args.get(i_templates+1).expect("--templates needs a file name");
+        let template_data: String = fs::read_to_string(template_file).expect("Cannot open file {template_file}");
+        let mode: SymmetryMode = if args.iter().any(|arg| arg == "--reflect") {
+            SymmetryMode::RotationsAndReflections
+        } else if args.iter().any(|arg| arg == "--rotate") {
+            SymmetryMode::Rotations
+        } else {
+            SymmetryMode::Identity
+        };
+        print_template_matches(&word_search, &parse_templates(&template_data), mode);
+        return
+    }
+
     let num_matches: usize = find_all_matches(&word_search);
 
     let after = before.elapsed();
@@ -120,3 +359,67 @@ fn test_small_word_search_xmas() {
 
     assert_eq!(9, num_matches);
 }
+
+#[test]
+fn test_find_words() {
+    let filename: &str = "test_input.txt";
+    let filedata: String = fs::read_to_string(filename).expect("Cannot open file {filename}");
+
+    let word_search: Vec<Vec<char>> = filedata.lines()
+        .map(|line| line.chars().collect())
+        .collect();
+
+    let matches: Vec<WordMatch> = find_words(&word_search, &["XMAS"], SearchOptions::default());
+    assert_eq!(18, matches.len());
+    assert!(matches.contains(&WordMatch { word: String::from("XMAS"), start: [0, 5], direction: [0, 1] }));
+    assert!(matches.contains(&WordMatch { word: String::from("XMAS"), start: [9, 9], direction: [-1, -1] }));
+
+    // "XMA" is a prefix of "XMAS" and is found on every one of its rays (plus some more)
+    let matches: Vec<WordMatch> = find_words(&word_search, &["XMAS", "XMA"], SearchOptions::default());
+    assert_eq!(18, matches.iter().filter(|found| found.word == "XMAS").count());
+    assert!(matches.iter().filter(|found| found.word == "XMA").count() >= 18);
+
+    let options = SearchOptions { wrap_around: false, fold_case: true };
+    assert_eq!(18, find_words(&word_search, &["xmas"], options).len());
+    assert_eq!(0, find_words(&word_search, &["xmas"], SearchOptions::default()).len());
+}
+
+#[test]
+fn test_find_words_wrap_around() {
+    let word_search: Vec<Vec<char>> = ["ASXM", "ZZZZ"].iter().map(|line| line.chars().collect()).collect();
+
+    assert_eq!(0, find_words(&word_search, &["XMAS"], SearchOptions::default()).len());
+
+    let options = SearchOptions { wrap_around: true, fold_case: false };
+    let matches: Vec<WordMatch> = find_words(&word_search, &["XMAS"], options);
+    assert_eq!(vec![WordMatch { word: String::from("XMAS"), start: [0, 2], direction: [0, 1] }], matches);
+}
+
+#[test]
+fn test_templates() {
+    let filename: &str = "test_input.txt";
+    let filedata: String = fs::read_to_string(filename).expect("Cannot open file {filename}");
+
+    let word_search: Vec<Vec<char>> = filedata.lines()
+        .map(|line| line.chars().collect())
+        .collect();
+
+    let templates: Vec<Template> = parse_templates("M.S\n.A.\nM.S\n\n\nXMAS\n");
+    assert_eq!(2, templates.len());
+    assert_eq!(vec![Some('M'), None, Some('S')], templates[0].cells[0]);
+
+    // both templates have four distinct rotations, and reflections add nothing new
+    assert_eq!(4, templates[0].variants(SymmetryMode::Rotations).len());
+    assert_eq!(4, templates[0].variants(SymmetryMode::RotationsAndReflections).len());
+    assert_eq!(4, templates[1].variants(SymmetryMode::RotationsAndReflections).len());
+
+    let matches: Vec<TemplateMatch> = match_templates(&word_search, &templates[..1], SymmetryMode::Rotations);
+    assert_eq!(9, matches.len());
+    let identity = Symmetry { reflected: false, quarter_turns: 0 };
+    assert!(matches.contains(&TemplateMatch { template_index: 0, top_left: [0, 1], symmetry: identity }));
+
+    // only the horizontal and vertical XMAS's, the diagonal ones are not a symmetry of the grid
+    let matches: Vec<TemplateMatch> = match_templates(&word_search, &templates[1..], SymmetryMode::RotationsAndReflections);
+    assert_eq!(8, matches.len());
+    assert_eq!(3, match_templates(&word_search, &templates[1..], SymmetryMode::Identity).len());
+}
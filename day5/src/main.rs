@@ -1,5 +1,6 @@
 use std::fs;
-use std::collections::HashMap;
+use std::env;
+use std::collections::{HashMap, HashSet};
 use std::cmp::Ordering;
 use std::time::Instant;
 
@@ -67,6 +68,294 @@ fn middle_page(vec: &Vec<usize>) -> usize {
     vec[len/2]
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OrderError {
+    Cycle(Vec<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UpdateReport {
+    violations: Vec<[usize; 2]>,
+    ambiguous: Option<[usize; 2]>,
+    order: Result<Vec<usize>, OrderError>,
+}
+
+// only the rules between pages of this update matter, rules through other pages are ignored
+fn induced_rules(update: &[usize], ordering_pairs: &[[usize; 2]]) -> Vec<[usize; 2]> {
+    let pages: HashSet<usize> = update.iter().copied().collect();
+    ordering_pairs.iter()
+        .filter(|[num1, num2]| pages.contains(num1) && pages.contains(num2))
+        .copied()
+        .collect()
+}
+
+fn rule_violations(update: &[usize], ordering_pairs: &[[usize; 2]]) -> Vec<[usize; 2]> {
+    let position: HashMap<usize, usize> = update.iter().enumerate().map(|(i, &page)| (page, i)).collect();
+    induced_rules(update, ordering_pairs).into_iter()
+        .filter(|[num1, num2]| position[num1] > position[num2])
+        .collect()
+}
+
+// Kahn's algorithm; among the pages which are free to go next, the one earliest in the update is
+// picked, so a correctly ordered update comes back unchanged
+fn topological_order(update: &[usize], ordering_pairs: &[[usize; 2]]) -> (Result<Vec<usize>, OrderError>, Option<[usize; 2]>) {
+    let rules: Vec<[usize; 2]> = induced_rules(update, ordering_pairs);
+    let mut num_before: HashMap<usize, usize> = update.iter().map(|&page| (page, 0)).collect();
+    let mut after: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &[num1, num2] in rules.iter() {
+        *num_before.get_mut(&num2).unwrap() += 1;
+        after.entry(num1).or_default().push(num2);
+    }
+
+    let mut order: Vec<usize> = Vec::new();
+    let mut ambiguous: Option<[usize; 2]> = None;
+    let mut remaining: Vec<usize> = update.to_vec();
+    loop {
+        let ready: Vec<usize> = remaining.iter().copied().filter(|page| num_before[page] == 0).collect();
+        let next: usize = match ready[..] {
+            [] => break,
+            [page] => page,
+            [page1, page2, ..] => { ambiguous.get_or_insert([page1, page2]); page1 },
+        };
+        remaining.retain(|&page| page != next);
+        order.push(next);
+        for page in after.get(&next).into_iter().flatten() {
+            *num_before.get_mut(page).unwrap() -= 1;
+        }
+    }
+
+    if remaining.is_empty() {
+        return (Ok(order), ambiguous)
+    }
+    (Err(OrderError::Cycle(find_cycle(&remaining, &rules))), ambiguous)
+}
+
+// every page left over by Kahn's algorithm has a rule from another left-over page, so walking those
+// rules backwards must eventually come back around
+fn find_cycle(remaining: &[usize], rules: &[[usize; 2]]) -> Vec<usize> {
+    let remaining: HashSet<usize> = remaining.iter().copied().collect();
+    let before: HashMap<usize, usize> = rules.iter()
+        .filter(|[num1, num2]| remaining.contains(num1) && remaining.contains(num2))
+        .map(|&[num1, num2]| (num2, num1))
+        .collect();
+
+    let mut path: Vec<usize> = vec![*remaining.iter().min().unwrap()];
+    loop {
+        let previous: usize = before[path.last().unwrap()];
+        if let Some(start) = path.iter().position(|&page| page == previous) {
+            let mut cycle: Vec<usize> = path.split_off(start);
+            cycle.reverse();
+            let i_min: usize = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+            cycle.rotate_left(i_min);
+            return cycle
+        }
+        path.push(previous);
+    }
+}
+
+fn validate_update(update: &[usize], ordering_pairs: &[[usize; 2]]) -> UpdateReport {
+    let violations: Vec<[usize; 2]> = rule_violations(update, ordering_pairs);
+    let (order, ambiguous) = topological_order(update, ordering_pairs);
+    UpdateReport { violations, ambiguous, order }
+}
+
+fn print_validation(entries: &[Vec<usize>], ordering_pairs: &[[usize; 2]]) {
+    for (i, entry) in entries.iter().enumerate() {
+        let UpdateReport { violations, ambiguous, order } = validate_update(entry, ordering_pairs);
+        let violations: Vec<String> = violations.iter().map(|[num1, num2]| format!("{num1}|{num2}")).collect();
+        println!("update {i}: {entry:?}");
+        if !violations.is_empty() { println!("    violates: {}", violations.join(", ")) }
+        if let Some([page1, page2]) = ambiguous { println!("    ambiguous: no rule orders {page1} and {page2}") }
+        match order {
+            Ok(order) => println!("    order: {order:?}"),
+            Err(OrderError::Cycle(cycle)) => println!("    error: rules form a cycle {cycle:?}"),
+        }
+    }
+}
+
+fn reachable_without(rules: &[[usize; 2]], skip: usize) -> bool {
+    let [start, target] = rules[skip];
+    let mut seen: HashSet<usize> = HashSet::from([start]);
+    let mut stack: Vec<usize> = vec![start];
+    while let Some(page) = stack.pop() {
+        for (i, &[num1, num2]) in rules.iter().enumerate() {
+            if i == skip || num1 != page || !seen.insert(num2) { continue }
+            if num2 == target { return true }
+            stack.push(num2);
+        }
+    }
+    false
+}
+
+// rules are dropped one by one whenever what they say already follows from the remaining rules, so
+// the set of reachable pages never changes (this also works if the rules have cycles)
+fn transitive_reduction(rules: &[[usize; 2]]) -> Vec<[usize; 2]> {
+    let mut reduced: Vec<[usize; 2]> = rules.to_vec();
+    let mut i: usize = 0;
+    while i < reduced.len() {
+        if reachable_without(&reduced, i) { reduced.remove(i); } else { i += 1 }
+    }
+    reduced
+}
+
+fn rules_to_dot(ordering_pairs: &[[usize; 2]], update: Option<&[usize]>, reduce: bool) -> String {
+    let mut rules: Vec<[usize; 2]> = match update {
+        Some(update) => induced_rules(update, ordering_pairs),
+        None => ordering_pairs.to_vec(),
+    };
+    rules.sort();
+    rules.dedup();
+    if reduce { rules = transitive_reduction(&rules) }
+    let violations: Vec<[usize; 2]> = update.map_or(Vec::new(), |update| rule_violations(update, ordering_pairs));
+
+    let mut dot: String = String::from("digraph rules {\n");
+    if let Some(update) = update {
+        // pages without any rule should still show up
+        for page in update.iter() { dot += &format!("    {page};\n") }
+    }
+    for [num1, num2] in rules.iter() {
+        let style: &str = if violations.contains(&[*num1, *num2]) { " [color=red]" } else { "" };
+        dot += &format!("    {num1} -> {num2}{style};\n");
+    }
+    // a reduced rule can be violated even if its replacement path is not drawn red
+    for [num1, num2] in violations.iter().filter(|pair| !rules.contains(pair)) {
+        dot += &format!("    {num1} -> {num2} [color=red, style=dashed];\n");
+    }
+    dot += "}\n";
+    dot
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PageMove {
+    page: usize,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Repair {
+    order: Vec<usize>,
+    kept: Vec<usize>,
+    moves: Vec<PageMove>,
+    swaps: Vec<[usize; 2]>,
+}
+
+// Kuhn's augmenting path step of the bipartite matching, from the left copy of page i
+fn augment(i: usize, edges: &[Vec<usize>], matched_left: &mut [Option<usize>], visited: &mut [bool]) -> bool {
+    for &j in edges[i].iter() {
+        if visited[j] { continue }
+        visited[j] = true;
+        if matched_left[j].is_none_or(|k| augment(k, edges, matched_left, visited)) {
+            matched_left[j] = Some(i);
+            return true
+        }
+    }
+    false
+}
+
+// indices of the largest set of pages which can stay in their current relative order. Two pages
+// conflict when the earlier one has to come after the later one by the transitive closure of the
+// rules; conflicts form a partial order, and the pages which may stay are its largest antichain.
+// That one comes from a minimum vertex cover of the chain matching (Dilworth and König)
+fn largest_consistent_subset(update: &[usize], ordering_pairs: &[[usize; 2]]) -> Vec<usize> {
+    let size: usize = update.len();
+    let position: HashMap<usize, usize> = update.iter().enumerate().map(|(i, &page)| (page, i)).collect();
+    let mut reach: Vec<Vec<bool>> = vec![vec![false; size]; size];
+    for [num1, num2] in induced_rules(update, ordering_pairs) {
+        reach[position[&num1]][position[&num2]] = true;
+    }
+    for k in 0..size {
+        let through: Vec<bool> = reach[k].clone();
+        for row in reach.iter_mut().filter(|row| row[k]) {
+            row.iter_mut().zip(through.iter()).for_each(|(cell, &step)| *cell |= step);
+        }
+    }
+
+    // i conflicts with a later j when j has to come before i
+    let edges: Vec<Vec<usize>> = (0..size)
+        .map(|i| ((i + 1)..size).filter(|&j| reach[j][i]).collect())
+        .collect();
+    let mut matched_left: Vec<Option<usize>> = vec![None; size];
+    for i in 0..size {
+        augment(i, &edges, &mut matched_left, &mut vec![false; size]);
+    }
+
+    // alternating paths from the unmatched left pages
+    let mut has_match: Vec<bool> = vec![false; size];
+    matched_left.iter().flatten().for_each(|&i| has_match[i] = true);
+    let mut left_reached: Vec<bool> = has_match.iter().map(|&matched| !matched).collect();
+    let mut right_reached: Vec<bool> = vec![false; size];
+    let mut stack: Vec<usize> = (0..size).filter(|&i| left_reached[i]).collect();
+    while let Some(i) = stack.pop() {
+        for &j in edges[i].iter() {
+            if right_reached[j] { continue }
+            right_reached[j] = true;
+            if let Some(k) = matched_left[j].filter(|&k| !left_reached[k]) {
+                left_reached[k] = true;
+                stack.push(k);
+            }
+        }
+    }
+
+    // the cover is the unreached left pages and the reached right pages
+    (0..size).filter(|&i| left_reached[i] && !right_reached[i]).collect()
+}
+
+// the pages which may stay put are the largest set already in an order the rules allow, every other
+// page has to be moved once. The repaired order keeps those pages in place relative to each other;
+// swaps follow the cycles of the permutation instead
+fn repair_update(update: &[usize], ordering_pairs: &[[usize; 2]]) -> Result<Repair, OrderError> {
+    topological_order(update, ordering_pairs).0?;
+    let kept: Vec<usize> = largest_consistent_subset(update, ordering_pairs).into_iter().map(|i| update[i]).collect();
+    let mut pairs: Vec<[usize; 2]> = ordering_pairs.to_vec();
+    pairs.extend(kept.windows(2).map(|pair| [pair[0], pair[1]]));
+    let order: Vec<usize> = topological_order(update, &pairs).0?;
+
+    let mut current: Vec<usize> = update.to_vec();
+    let mut placed: HashSet<usize> = kept.iter().copied().collect();
+    let mut moves: Vec<PageMove> = Vec::new();
+    for (i, &page) in order.iter().enumerate() {
+        if placed.contains(&page) { continue }
+        let from: usize = current.iter().position(|&other| other == page).unwrap();
+        current.remove(from);
+        let to: usize = match order[..i].iter().rev().find(|other| placed.contains(other)) {
+            Some(before) => current.iter().position(|other| other == before).unwrap() + 1,
+            None => 0,
+        };
+        current.insert(to, page);
+        placed.insert(page);
+        moves.push(PageMove { page, from, to });
+    }
+
+    let mut current: Vec<usize> = update.to_vec();
+    let mut swaps: Vec<[usize; 2]> = Vec::new();
+    for i in 0..current.len() {
+        if current[i] == order[i] { continue }
+        let j: usize = current.iter().position(|&page| page == order[i]).unwrap();
+        current.swap(i, j);
+        swaps.push([i, j]);
+    }
+
+    Ok(Repair { order, kept, moves, swaps })
+}
+
+fn print_repairs(entries: &[Vec<usize>], ordering_pairs: &[[usize; 2]]) {
+    for (i, entry) in entries.iter().enumerate() {
+        match repair_update(entry, ordering_pairs) {
+            Ok(repair) if repair.moves.is_empty() => continue,
+            Ok(Repair { order, kept, moves, swaps }) => {
+                println!("update {i}: {entry:?} -> {order:?} (middle page {})", middle_page(&order));
+                println!("    {} moves, keeping {kept:?}", moves.len());
+                for PageMove { page, from, to } in moves.iter() {
+                    println!("        move {page} from {from} to {to}");
+                }
+                println!("    {} swaps: {swaps:?}", swaps.len());
+            },
+            Err(OrderError::Cycle(cycle)) => println!("update {i}: {entry:?} cannot be repaired, rules form a cycle {cycle:?}"),
+        }
+    }
+}
+
 fn main() {
     let filename: &str = "input.txt";
     let textdata: String = fs::read_to_string(filename)
@@ -75,6 +364,23 @@ fn main() {
     let before = Instant::now();
 
     let (ordering_pairs, entries) = data_from_text(textdata);
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "--validate") {
+        print_validation(&entries, &ordering_pairs);
+        return
+    }
+    if args.iter().any(|arg| arg == "--repair") {
+        print_repairs(&entries, &ordering_pairs);
+        return
+    }
+    if let Some(i_dot) = args.iter().position(|arg| arg == "--dot") {
+        let update: Option<&[usize]> = args.get(i_dot+1)
+            .and_then(|arg| arg.parse::<usize>().ok())
+            .map(|i_update| entries.get(i_update).expect("Update index out of range").as_slice());
+        let reduce: bool = args.iter().any(|arg| arg == "--reduce");
+        print!("{}", rules_to_dot(&ordering_pairs, update, reduce));
+        return
+    }
     let ordering = ordering_map(&ordering_pairs);
 
     let mut sorted_entries = entries.clone();
@@ -162,3 +468,113 @@ fn test_num() {
         .sum();
     assert_eq!(143, sum_middle_pages);
 }
+
+#[test]
+fn test_validate() {
+    let filename: &str = "test_input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+
+    let (ordering_pairs, entries) = data_from_text(textdata);
+
+    let report: UpdateReport = validate_update(&entries[0], &ordering_pairs);
+    assert!(report.violations.is_empty());
+    assert_eq!(None, report.ambiguous);
+    assert_eq!(Ok(entries[0].clone()), report.order);
+
+    assert_eq!(vec![[97, 75]], rule_violations(&entries[3], &ordering_pairs));
+    assert_eq!(vec![[29, 13]], rule_violations(&entries[4], &ordering_pairs));
+    assert_eq!(Ok(vec![97, 75, 47, 29, 13]), validate_update(&entries[5], &ordering_pairs).order);
+
+    // every update of the example is totally ordered by the rules
+    for entry in entries.iter() {
+        let report: UpdateReport = validate_update(entry, &ordering_pairs);
+        assert_eq!(None, report.ambiguous);
+        assert!(report.order.is_ok());
+    }
+}
+
+#[test]
+fn test_validate_cycle() {
+    let ordering_pairs: Vec<[usize; 2]> = vec![[1, 2], [2, 3], [3, 1], [4, 1], [5, 6]];
+
+    let report: UpdateReport = validate_update(&[4, 3, 2, 1], &ordering_pairs);
+    assert_eq!(vec![[1, 2], [2, 3]], report.violations);
+    assert_eq!(Err(OrderError::Cycle(vec![1, 2, 3])), report.order);
+
+    let report: UpdateReport = validate_update(&[6, 1, 5], &ordering_pairs);
+    assert_eq!(Some([1, 5]), report.ambiguous);
+    assert_eq!(Ok(vec![1, 5, 6]), report.order);
+}
+
+#[test]
+fn test_dot() {
+    let filename: &str = "test_input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+
+    let (ordering_pairs, entries) = data_from_text(textdata);
+
+    let dot: String = rules_to_dot(&ordering_pairs, None, false);
+    assert_eq!(21, dot.matches(" -> ").count());
+    assert!(!dot.contains("red"));
+
+    // the example rules are a total order of 7 pages, which reduces to a chain of 6 rules
+    assert_eq!(6, transitive_reduction(&ordering_pairs).len());
+
+    let dot: String = rules_to_dot(&ordering_pairs, Some(&entries[3]), false);
+    assert_eq!(10, dot.matches(" -> ").count());
+    assert!(dot.contains("    97 -> 75 [color=red];\n"));
+    assert_eq!(1, dot.matches("red").count());
+
+    let dot: String = rules_to_dot(&ordering_pairs, Some(&entries[5]), true);
+    assert_eq!("digraph rules {\n    97;\n    13;\n    75;\n    29;\n    47;\n    \
+                29 -> 13 [color=red];\n    47 -> 29 [color=red];\n    75 -> 47;\n    97 -> 75;\n    \
+                47 -> 13 [color=red, style=dashed];\n    75 -> 13 [color=red, style=dashed];\n}\n", dot);
+}
+
+#[test]
+fn test_repair() {
+    let filename: &str = "test_input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+
+    let (ordering_pairs, entries) = data_from_text(textdata);
+
+    let repair: Repair = repair_update(&entries[0], &ordering_pairs).unwrap();
+    assert!(repair.moves.is_empty() && repair.swaps.is_empty());
+
+    let repair: Repair = repair_update(&entries[3], &ordering_pairs).unwrap();
+    assert_eq!(vec![PageMove { page: 75, from: 0, to: 1 }], repair.moves);
+    assert_eq!(vec![[0, 1]], repair.swaps);
+
+    // 97,13,75,29,47 -> 97,75,47,29,13: keep 97,75,47 (or 97,75,29), move the other two
+    let repair: Repair = repair_update(&entries[5], &ordering_pairs).unwrap();
+    assert_eq!(vec![97, 75, 47, 29, 13], repair.order);
+    assert_eq!(3, repair.kept.len());
+    assert_eq!(2, repair.moves.len());
+    assert_eq!(2, repair.swaps.len());
+
+    // applying the moves has to give the repaired order
+    for entry in entries.iter() {
+        let repair: Repair = repair_update(entry, &ordering_pairs).unwrap();
+        let mut current: Vec<usize> = entry.clone();
+        for PageMove { page, from, to } in repair.moves.iter() {
+            assert_eq!(*page, current.remove(*from));
+            current.insert(*to, *page);
+        }
+        assert_eq!(repair.order, current);
+        assert_eq!(entry.len() - repair.kept.len(), repair.moves.len());
+    }
+
+    // the rules leave 2 free, so moving 4 to the front is enough
+    let repair: Repair = repair_update(&[3, 1, 2, 4], &[[4, 3], [3, 1]]).unwrap();
+    assert_eq!(vec![3, 1, 2], repair.kept);
+    assert_eq!(vec![PageMove { page: 4, from: 3, to: 0 }], repair.moves);
+    assert_eq!(vec![4, 3, 1, 2], repair.order);
+
+    // 2 only conflicts with 3 and 1 through 2 -> 4 -> 3 -> 1
+    let repair: Repair = repair_update(&[3, 1, 2, 4, 5], &[[2, 4], [4, 3], [3, 1]]).unwrap();
+    assert_eq!(2, repair.moves.len());
+    assert_eq!(vec![2, 4, 3, 1, 5], repair.order);
+}
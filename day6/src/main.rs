@@ -1,16 +1,140 @@
 use std::fs;
+use std::env;
+use std::collections::HashMap;
 use std::time::Instant;
 
-const UP: u8 = 2u8.pow(0) + 2u8.pow(4);
-const RIGHT: u8 = 2u8.pow(1) + 2u8.pow(5);
-const DOWN: u8 = 2u8.pow(2) + 2u8.pow(6);
-const LEFT: u8 = 2u8.pow(3) + 2u8.pow(7);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Facing {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Facing {
+    const ALL: [Facing; 4] = [Facing::Up, Facing::Right, Facing::Down, Facing::Left];
 
-const START_DIR: u8 = UP;
+    fn turn(self, rule: TurnRule) -> Facing {
+        let num_quarter_turns: usize = match rule {
+            TurnRule::Right => 1,
+            TurnRule::UTurn => 2,
+            TurnRule::Left => 3,
+        };
+        Facing::ALL[(self.index() + num_quarter_turns) % 4]
+    }
+
+    fn from_char(chr: char) -> Option<Facing> {
+        match chr {
+            '^' => Some(Facing::Up),
+            '>' => Some(Facing::Right),
+            'v' => Some(Facing::Down),
+            '<' => Some(Facing::Left),
+            _ => None,
+        }
+    }
+
+    fn step(self) -> [isize; 2] {
+        match self {
+            Facing::Up    => [-1,  0],
+            Facing::Right => [ 0,  1],
+            Facing::Down  => [ 1,  0],
+            Facing::Left  => [ 0, -1],
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TurnRule {
+    Left,
+    Right,
+    UTurn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Guard {
+    pos: [usize; 2],
+    facing: Facing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Move {
+    Step,
+    Turn,
+}
+
+impl Guard {
+    fn next(self, obstacles: &[Vec<bool>]) -> Option<(Guard, Move)> {
+        self.next_with(obstacles, TurnRule::Right)
+    }
+
+    // None once the guard walks off the map
+    fn next_with(self, obstacles: &[Vec<bool>], rule: TurnRule) -> Option<(Guard, Move)> {
+        let map_size: [usize; 2] = [obstacles.len(), obstacles[0].len()];
+        let [next_row, next_col] = next_in_bound(self.pos, self.facing.step(), map_size).ok()?;
+        if obstacles[next_row][next_col] {
+            Some((Guard { pos: self.pos, facing: self.facing.turn(rule) }, Move::Turn))
+        } else {
+            Some((Guard { pos: [next_row, next_col], facing: self.facing }, Move::Step))
+        }
+    }
+}
+
+// per cell and facing, the index into the trace at which the guard was first there
+struct Visited {
+    num_cols: usize,
+    first_seen: Vec<[Option<usize>; 4]>,
+}
+
+impl Visited {
+    fn new(map_size: [usize; 2]) -> Self {
+        Visited { num_cols: map_size[1], first_seen: vec![[None; 4]; map_size[0] * map_size[1]] }
+    }
+
+    // returns the earlier index if the guard has been in this exact state before
+    fn insert(&mut self, guard: Guard, index: usize) -> Option<usize> {
+        let [row, col] = guard.pos;
+        let entry = &mut self.first_seen[row * self.num_cols + col][guard.facing.index()];
+        match *entry {
+            Some(earlier) => Some(earlier),
+            None => { *entry = Some(index); None },
+        }
+    }
+
+    fn contains_pos(&self, [row, col]: [usize; 2]) -> bool {
+        self.first_seen[row * self.num_cols + col].iter().any(Option::is_some)
+    }
+
+    fn positions(&self) -> Vec<[usize; 2]> {
+        (0..self.first_seen.len())
+            .map(|cell| [cell / self.num_cols, cell % self.num_cols])
+            .filter(|&pos| self.contains_pos(pos))
+            .collect()
+    }
+}
 
 enum HistoryResult {
-    Terminated(Vec<Vec<u8>>),
-    Infinite(Vec<Vec<u8>>),
+    Terminated(Visited),
+    Infinite { visited: Visited, start: usize, period: usize },
+}
+
+impl HistoryResult {
+    fn visited(&self) -> &Visited {
+        match self {
+            HistoryResult::Terminated(visited) => visited,
+            HistoryResult::Infinite { visited, .. } => visited,
+        }
+    }
+}
+
+// guards[0] is the start, moves[i] takes guards[i] to guards[i+1]
+struct Trace {
+    guards: Vec<Guard>,
+    moves: Vec<Move>,
+    result: HistoryResult,
 }
 
 fn next_in_bound(pos: [usize; 2], dir: [isize; 2], map_size: [usize; 2]) -> Result<[usize; 2], &'static str> {
@@ -28,61 +152,351 @@ fn next_in_bound(pos: [usize; 2], dir: [isize; 2], map_size: [usize; 2]) -> Resu
     }
 }
 
-fn get_start_pos_dir(filedata: &String) -> ([usize; 2], u8) {
+fn get_start_guard(filedata: &str) -> Guard {
     // get current position of guard; assume initial direction is "up"
     let row: usize = filedata.lines().position(|string| { string.contains('^') }).expect("'^' not found");
     let col: usize = filedata.lines().nth(row).unwrap().chars().position(|chr| chr=='^').unwrap();
-    let pos: [usize; 2] = [row, col];
 
-    (pos, START_DIR)
+    Guard { pos: [row, col], facing: Facing::Up }
 }
 
-fn simulate_history(start_pos: &[usize; 2], start_dir: u8, obstacles: &Vec<Vec<bool>>) -> HistoryResult {
-    // initialize maze history
-    let map_size: [usize; 2] = [obstacles.len(), obstacles[0].len()];
-    let mut history: Vec<Vec<u8>> = (0..map_size[0])
-        .map(|_| (0..map_size[1]).map(|_| 0u8).collect::<Vec<u8>>())
-        .collect::<Vec<Vec<u8>>>();
+fn get_start_guards(filedata: &str) -> Vec<Guard> {
+    filedata.lines().enumerate()
+        .flat_map(|(row, string)| string.chars().enumerate()
+            .filter_map(move |(col, chr)| Facing::from_char(chr).map(|facing| Guard { pos: [row, col], facing }))
+        )
+        .collect()
+}
 
-    let mut current_dir = start_dir;
-    let mut current_pos = *start_pos;
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GuardStatus {
+    Patrolling,
+    Exited(usize),
+    Collided(usize, Vec<usize>),
+    // start tick and period of the guard's own repeating state
+    Looping { start: usize, period: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PatrolResult {
+    statuses: Vec<GuardStatus>,
+    num_ticks: usize,
+    // guards going round the same loop (at any phase), only groups of two or more
+    shared_loops: Vec<Vec<usize>>,
+}
+
+fn gcd(num1: usize, num2: usize) -> usize {
+    let (mut num1, mut num2) = (num1, num2);
+    while num2 != 0 { (num1, num2) = (num2, num1 % num2) }
+    num1
+}
+
+// the tick at which the state of all looping guards together first repeats: each is on its own
+// loop from its start on, so they all are from the last start, and line up again after the lcm of
+// the periods. 0 if nobody is looping, None if the lcm does not fit in usize
+fn combined_repeat(statuses: &[GuardStatus]) -> Option<usize> {
+    let [last_start, combined_period] = statuses.iter()
+        .try_fold([0, 0], |[last_start, combined_period], status| match *status {
+            GuardStatus::Looping { start, period } if combined_period == 0 => Some([last_start.max(start), period]),
+            GuardStatus::Looping { start, period } => {
+                let combined_period: usize = (combined_period / gcd(combined_period, period)).checked_mul(period)?;
+                Some([last_start.max(start), combined_period])
+            },
+            _ => Some([last_start, combined_period]),
+        })?;
+    last_start.checked_add(combined_period)
+}
+
+// all guards move (step or turn) once per tick. Guards which end up on the same cell, or walk
+// through each other, collide and stop where they are, as an obstacle for everybody else. Loops are
+// found per guard, from its own repeated state, but two looping guards can still meet later on, so
+// the patrol only ends once the state of all the guards together repeats. A new wreck changes the
+// map, so then all loop detection starts over
+fn simulate_patrol(starts: &[Guard], rules: &[TurnRule], obstacles: &[Vec<bool>]) -> PatrolResult {
+    let mut obstacles: Vec<Vec<bool>> = obstacles.to_vec();
+    let mut guards: Vec<Option<Guard>> = starts.iter().copied().map(Some).collect();
+    let mut statuses: Vec<GuardStatus> = vec![GuardStatus::Patrolling; starts.len()];
+    let mut seen: Vec<HashMap<Guard, usize>> = vec![HashMap::new(); starts.len()];
+    let mut loops: Vec<Vec<Guard>> = vec![Vec::new(); starts.len()];
+
+    let mut tick: usize = 0;
+    loop {
+        for (i, guard) in guards.iter().enumerate() {
+            let Some(guard) = guard else { continue };
+            if statuses[i] != GuardStatus::Patrolling { continue }
+            if let Some(&start) = seen[i].get(guard) {
+                statuses[i] = GuardStatus::Looping { start, period: tick - start };
+                let mut states: Vec<(Guard, usize)> = seen[i].iter().filter(|(_, &seen_tick)| seen_tick >= start).map(|(&state, &seen_tick)| (state, seen_tick)).collect();
+                states.sort_by_key(|&(_, seen_tick)| seen_tick);
+                loops[i] = states.into_iter().map(|(state, _)| state).collect();
+            } else {
+                seen[i].insert(*guard, tick);
+            }
+        }
+        if statuses.iter().all(|status| *status != GuardStatus::Patrolling)
+            && combined_repeat(&statuses).is_some_and(|repeat| tick >= repeat) { break }
+        tick += 1;
+
+        let mut next_guards: Vec<Option<Guard>> = guards.iter().zip(rules.iter())
+            .map(|(guard, &rule)| guard.and_then(|guard| guard.next_with(&obstacles, rule)).map(|(next, _)| next))
+            .collect();
+        for (i, (guard, next)) in guards.iter().zip(next_guards.iter()).enumerate() {
+            if guard.is_some() && next.is_none() { statuses[i] = GuardStatus::Exited(tick) }
+        }
+
+        let crashes: Vec<(usize, Vec<usize>)> = (0..guards.len())
+            .filter_map(|i| {
+                let next: Guard = next_guards[i]?;
+                let others: Vec<usize> = (0..guards.len())
+                    .filter(|&j| j != i)
+                    .filter(|&j| match (next_guards[j], guards[j], guards[i]) {
+                        (Some(other_next), Some(other), Some(guard)) => {
+                            let swapped: bool = other_next.pos == guard.pos && other.pos == next.pos;
+                            other_next.pos == next.pos || swapped
+                        },
+                        _ => false,
+                    })
+                    .collect();
+                (!others.is_empty()).then_some((i, others))
+            })
+            .collect();
+        if !crashes.is_empty() {
+            for status in statuses.iter_mut().filter(|status| matches!(status, GuardStatus::Looping { .. })) {
+                *status = GuardStatus::Patrolling;
+            }
+            seen.iter_mut().for_each(HashMap::clear);
+            loops.iter_mut().for_each(Vec::clear);
+        }
+        for (i, others) in crashes {
+            let [row, col] = next_guards[i].unwrap().pos;
+            obstacles[row][col] = true;
+            statuses[i] = GuardStatus::Collided(tick, others);
+            next_guards[i] = None;
+        }
+        guards = next_guards;
+    }
+
+    PatrolResult { statuses, num_ticks: tick, shared_loops: shared_loops(&loops) }
+}
+
+fn shared_loops(loops: &[Vec<Guard>]) -> Vec<Vec<usize>> {
+    let mut shared: Vec<(Vec<Guard>, Vec<usize>)> = Vec::new();
+    for (i, states) in loops.iter().enumerate() {
+        if states.is_empty() { continue }
+        let mut states: Vec<Guard> = states.clone();
+        states.sort_by_key(|guard| (guard.pos, guard.facing.index()));
+        match shared.iter_mut().find(|(other_states, _)| *other_states == states) {
+            Some((_, members)) => members.push(i),
+            None => shared.push((states, vec![i])),
+        }
+    }
+    shared.into_iter().map(|(_, members)| members).filter(|members| members.len() > 1).collect()
+}
+
+fn parse_turn_rule(string: &str) -> TurnRule {
+    match string {
+        "left" => TurnRule::Left,
+        "right" => TurnRule::Right,
+        "uturn" => TurnRule::UTurn,
+        _ => panic!("Unknown turn rule ({string}), use 'left', 'right' or 'uturn'"),
+    }
+}
+
+fn print_patrol(filedata: &str, obstacles: &[Vec<bool>], rules_arg: Option<&str>) {
+    let starts: Vec<Guard> = get_start_guards(filedata);
+    let mut rules: Vec<TurnRule> = rules_arg.unwrap_or("right").split(',').map(parse_turn_rule).collect();
+    if rules.len() == 1 { rules = vec![rules[0]; starts.len()] }
+    assert_eq!(starts.len(), rules.len(), "Give either one turn rule, or one per guard");
+
+    let PatrolResult { statuses, num_ticks, shared_loops } = simulate_patrol(&starts, &rules, obstacles);
+    for (i, (Guard { pos: [row, col], facing }, status)) in starts.iter().zip(statuses.iter()).enumerate() {
+        let status: String = match status {
+            GuardStatus::Patrolling => String::from("still patrolling"),
+            GuardStatus::Exited(tick) => format!("exits at tick {tick}"),
+            GuardStatus::Collided(tick, others) => format!("collides with {others:?} at tick {tick}"),
+            GuardStatus::Looping { start, period } => format!("loops from tick {start} with period {period}"),
+        };
+        println!("guard {i} from ({row}, {col}) facing {facing:?} turning {:?}: {status}", rules[i]);
+    }
+    println!("patrol settled after {num_ticks} ticks");
+    for members in shared_loops.iter() {
+        println!("guards {members:?} share a loop");
+    }
+}
+
+fn simulate_history(start: Guard, obstacles: &[Vec<bool>]) -> Trace {
+    let map_size: [usize; 2] = [obstacles.len(), obstacles[0].len()];
+    let mut visited = Visited::new(map_size);
+    let mut guards: Vec<Guard> = vec![start];
+    let mut moves: Vec<Move> = Vec::new();
 
     // loop while guard has not exited maze or stuck in infinite loop
-    // this is the complex part! Also interesting :)    
+    let mut guard: Guard = start;
     loop {
-        // when we loop, we've freshly arrive at the next point
-        let [row, col] = current_pos;
-        let check_history = &mut history[row][col];
-        let is_inf_loop = *check_history & current_dir != 0u8; // same location AND direction
-        if is_inf_loop { return HistoryResult::Infinite(history) }
-
-        *check_history += current_dir;
-
-        // prepare to move to next location
-        let next_step = match current_dir {
-            UP    => [-1,  0],
-            RIGHT => [ 0,  1],
-            DOWN  => [ 1,  0],
-            LEFT  => [ 0, -1],
-            _ => panic!("should not reach here!"), // invalid states are still representable
-                                                   // use of enums will make them irrepresentable
+        if let Some(start) = visited.insert(guard, guards.len() - 1) {
+            // the repeated state is not pushed twice, so the loop is guards[start..], and the move
+            // back into it is dropped with it
+            guards.pop();
+            moves.pop();
+            let period: usize = guards.len() - start;
+            return Trace { guards, moves, result: HistoryResult::Infinite { visited, start, period } }
+        }
+        let (next_guard, next_move) = match guard.next(obstacles) {
+            Some(next) => next,
+            None => break,
         };
+        guards.push(next_guard);
+        moves.push(next_move);
+        guard = next_guard;
+    }
+    Trace { guards, moves, result: HistoryResult::Terminated(visited) }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum JumpResult {
+    Exited,
+    Looped,
+}
+
+// for every cell and facing (up, right, down, left), the cell at which the guard has to turn next,
+// or None if it walks off the map instead. The guard jumps from turn to turn, and loops are found
+// from repeated turns alone
+struct JumpTable {
+    map_size: [usize; 2],
+    obstacles: Vec<bool>,
+    next_turn: Vec<[Option<usize>; 4]>,
+    seen: Vec<[u32; 4]>,
+    generation: u32,
+}
 
-        // testing for bounds
-        let [next_row, next_col] = match next_in_bound(current_pos, next_step, map_size) {
-            Ok(val) => val,
-            Err(_) => break,
+impl JumpTable {
+    fn new(obstacles: &[Vec<bool>]) -> Self {
+        let map_size: [usize; 2] = [obstacles.len(), obstacles[0].len()];
+        let num_cells: usize = map_size[0] * map_size[1];
+        let mut table = JumpTable {
+            map_size,
+            obstacles: obstacles.iter().flatten().copied().collect(),
+            next_turn: vec![[None; 4]; num_cells],
+            seen: vec![[0; 4]; num_cells],
+            generation: 0,
         };
+        (0..map_size[0]).for_each(|row| table.fill_row(row));
+        (0..map_size[1]).for_each(|col| table.fill_col(col));
+        table
+    }
 
-        // check for obstacles
-        let next_is_obstacle = obstacles[next_row][next_col];
-        if next_is_obstacle {
-            current_dir = current_dir.rotate_left(1);  // set-up as [L, D, R, U, L, D, R, U]
-        } else {
-            current_pos = [next_row, next_col];
+    // cells are given starting from the far end in the facing direction, i.e. against the direction
+    // the guard walks
+    fn fill_ray(&mut self, cells: impl Iterator<Item = usize>, i_dir: usize) {
+        let mut stop: Option<usize> = None;
+        let mut ahead_is_obstacle: bool = false;
+        for cell in cells {
+            if self.obstacles[cell] { ahead_is_obstacle = true; continue }
+            if ahead_is_obstacle { stop = Some(cell); ahead_is_obstacle = false }
+            self.next_turn[cell][i_dir] = stop;
+        }
+    }
+
+    fn fill_row(&mut self, row: usize) {
+        let num_cols: usize = self.map_size[1];
+        let cells = (0..num_cols).map(move |col| row * num_cols + col);
+        self.fill_ray(cells.clone().rev(), 1);
+        self.fill_ray(cells, 3);
+    }
+
+    fn fill_col(&mut self, col: usize) {
+        let num_cols: usize = self.map_size[1];
+        let cells = (0..self.map_size[0]).map(move |row| row * num_cols + col);
+        self.fill_ray(cells.clone(), 0);
+        self.fill_ray(cells.rev(), 2);
+    }
+
+    // only the row and column through the obstacle can change
+    fn set_obstacle(&mut self, [row, col]: [usize; 2], is_obstacle: bool) {
+        self.obstacles[row * self.map_size[1] + col] = is_obstacle;
+        self.fill_row(row);
+        self.fill_col(col);
+    }
+
+    fn simulate(&mut self, start: Guard) -> JumpResult {
+        self.generation += 1;
+        let mut cell: usize = start.pos[0] * self.map_size[1] + start.pos[1];
+        let mut i_dir: usize = start.facing.index();
+        loop {
+            if self.seen[cell][i_dir] == self.generation { return JumpResult::Looped }
+            self.seen[cell][i_dir] = self.generation;
+            cell = match self.next_turn[cell][i_dir] {
+                Some(stop) => stop,
+                None => return JumpResult::Exited,
+            };
+            i_dir = (i_dir + 1) % 4;
         }
     }
-    HistoryResult::Terminated(history)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Obstruction {
+    pos: [usize; 2],
+    first_hit: usize,
+    loop_length: usize,
+}
+
+// candidates are taken in the order the guard first walks into them, first_hit is the index of the
+// guard (in the unobstructed trace) which would bump into the obstruction
+fn find_loop_obstructions(start: Guard, obstacles: &[Vec<bool>]) -> Vec<Obstruction> {
+    let trace: Trace = simulate_history(start, obstacles);
+    let map_size: [usize; 2] = [obstacles.len(), obstacles[0].len()];
+    let mut tried: Vec<bool> = vec![false; map_size[0] * map_size[1]];
+    tried[start.pos[0] * map_size[1] + start.pos[1]] = true;
+
+    let mut jump_table = JumpTable::new(obstacles);
+    let mut new_obstacles: Vec<Vec<bool>> = obstacles.to_vec();
+    let mut found: Vec<Obstruction> = Vec::new();
+    for (first_hit, next_move) in trace.moves.iter().enumerate() {
+        let pos: [usize; 2] = trace.guards[first_hit+1].pos;
+        if *next_move == Move::Turn || tried[pos[0] * map_size[1] + pos[1]] { continue }
+        tried[pos[0] * map_size[1] + pos[1]] = true;
+
+        jump_table.set_obstacle(pos, true);
+        let is_loop: bool = jump_table.simulate(start) == JumpResult::Looped;
+        jump_table.set_obstacle(pos, false);
+        if !is_loop { continue }
+
+        // the jump table only knows turns, the full trace is needed for the length of the loop
+        new_obstacles[pos[0]][pos[1]] = true;
+        if let HistoryResult::Infinite { period, .. } = simulate_history(start, &new_obstacles).result {
+            found.push(Obstruction { pos, first_hit, loop_length: period });
+        }
+        new_obstacles[pos[0]][pos[1]] = false;
+    }
+    found
+}
+
+fn obstructions_to_csv(obstructions: &[Obstruction]) -> String {
+    let mut csv: String = String::from("row,col,first_hit,loop_length\n");
+    for Obstruction { pos: [row, col], first_hit, loop_length } in obstructions.iter() {
+        csv += &format!("{row},{col},{first_hit},{loop_length}\n");
+    }
+    csv
+}
+
+// same format as the puzzle example, with the obstructions drawn as 'O'
+fn obstructions_to_grid(filedata: &str, obstructions: &[Obstruction]) -> String {
+    let mut grid: Vec<Vec<char>> = filedata.lines().map(|line| line.chars().collect()).collect();
+    for Obstruction { pos: [row, col], .. } in obstructions.iter() {
+        grid[*row][*col] = 'O';
+    }
+    grid.iter().map(|line| line.iter().collect::<String>() + "\n").collect()
+}
+
+fn describe_trace(trace: &Trace) -> String {
+    let num_turns: usize = trace.moves.iter().filter(|&&next_move| next_move == Move::Turn).count();
+    let num_steps: usize = trace.moves.len() - num_turns;
+    let Guard { pos: [row, col], facing } = trace.guards.last().unwrap();
+    match trace.result {
+        HistoryResult::Terminated(_) => format!("{num_steps} steps, {num_turns} turns, exits from ({row}, {col}) facing {facing:?}"),
+        HistoryResult::Infinite { start, period, .. } => format!("{num_steps} steps, {num_turns} turns, loops from move {start} with period {period}"),
+    }
 }
 
 fn main() {
@@ -94,43 +508,43 @@ fn main() {
     let obstacles: Vec<Vec<bool>> = filedata.lines()
         .map(|string| string.chars().map(|chr| chr=='#').collect())
         .collect();
-    let (start_pos, start_dir) = get_start_pos_dir(&filedata);
 
-    let history: Vec<Vec<u8>> = match simulate_history(&start_pos, start_dir, &obstacles) {
-        HistoryResult::Terminated(val) => val,
-        HistoryResult::Infinite(val) => val,
-    };
+    // patrols find their own start positions, which need not include a '^'
+    if let Some(i_patrol) = env::args().position(|arg| arg == "--patrol") {
+        print_patrol(&filedata, &obstacles, env::args().nth(i_patrol+1).as_deref());
+        return
+    }
+    let start: Guard = get_start_guard(&filedata);
+    if let Some(i_format) = env::args().position(|arg| arg == "--obstructions") {
+        let obstructions: Vec<Obstruction> = find_loop_obstructions(start, &obstacles);
+        match env::args().nth(i_format+1).as_deref() {
+            Some("csv") => print!("{}", obstructions_to_csv(&obstructions)),
+            Some("grid") => print!("{}", obstructions_to_grid(&filedata, &obstructions)),
+            _ => panic!("--obstructions needs a format, either 'csv' or 'grid'"),
+        }
+        return
+    }
 
-    let num_visited: usize = history.iter()
-        .map(|vec| vec.iter().filter(|&&val| val != 0u8).count())
-        .sum();
+    let trace: Trace = simulate_history(start, &obstacles);
+    let pos_visited: Vec<[usize; 2]> = trace.result.visited().positions();
+    let num_visited: usize = pos_visited.len();
 
     let after = before.elapsed();
     println!("Time elapsed (Part I): {after:2?}");
     println!("(Part  I): num. of visited tiles: {num_visited}");
+    println!("    {}", describe_trace(&trace));
     let before = Instant::now();
 
-    let pos_visited: Vec<[usize; 2]> = history.iter().enumerate()
-        .flat_map(|(row, vec)| vec
-            .iter().enumerate()
-            .filter(|&(_, &val)| val != 0u8)  // ignore unvisited locations
-            .map( move |(col, _)| [row, col])
-        )
-        .filter(|&[row, col]| [row, col] != start_pos)  // ignore starting position
+    let pos_visited: Vec<[usize; 2]> = pos_visited.into_iter()
+        .filter(|&pos| pos != start.pos)  // ignore starting position
         .collect();
 
-    let [mut prev_row, mut prev_col] = start_pos;
-    let mut new_obstacles = obstacles;
+    let mut jump_table = JumpTable::new(&obstacles);
     let mut num_infinite_loops: usize = 0;
-    for [new_row, new_col] in pos_visited {
-        new_obstacles[prev_row][prev_col] = false;
-        new_obstacles[new_row][new_col] = true;
-
-        match simulate_history(&start_pos, start_dir, &new_obstacles) {
-            HistoryResult::Terminated(_) => (),
-            HistoryResult::Infinite(_) => num_infinite_loops += 1,
-        };
-        [prev_row, prev_col] = [new_row, new_col];
+    for new_pos in pos_visited {
+        jump_table.set_obstacle(new_pos, true);
+        if jump_table.simulate(start) == JumpResult::Looped { num_infinite_loops += 1 }
+        jump_table.set_obstacle(new_pos, false);
     }
     let after = before.elapsed();
     println!("Time elapsed (Part II): {after:2?}");
@@ -146,15 +560,185 @@ fn test_input() {
         .map(|string| string.chars().map(|chr| chr=='#').collect())
         .collect();
 
-    let (start_pos, start_dir) = get_start_pos_dir(&filedata);
+    let start: Guard = get_start_guard(&filedata);
 
-    let history = match simulate_history(&start_pos, start_dir, &obstacles) {
-        HistoryResult::Terminated(val) => val,
-        HistoryResult::Infinite(val) => val,
-    };
+    let trace: Trace = simulate_history(start, &obstacles);
+    assert!(matches!(trace.result, HistoryResult::Terminated(_)));
 
-    let num_visited: usize = history.iter()
-        .map(|vec| vec.iter().filter(|&&val| val != 0u8).count())
-        .sum();
+    let num_visited: usize = trace.result.visited().positions().len();
     assert_eq!(41, num_visited);
+
+    let num_turns: usize = trace.moves.iter().filter(|&&next_move| next_move == Move::Turn).count();
+    assert_eq!(trace.guards.len() - 1, trace.moves.len());
+    assert_eq!(10, num_turns);
+    assert_eq!(Guard { pos: [9, 7], facing: Facing::Down }, *trace.guards.last().unwrap());
+}
+
+#[test]
+fn test_jump_table() {
+    let filename: &str = "test_input.txt";
+    let filedata: String = fs::read_to_string(filename).expect("Cannot read file {filename}");
+
+    let obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+    let start: Guard = get_start_guard(&filedata);
+
+    // guard at the start walks up to (1, 4), just below the obstacle at (0, 4)
+    let mut jump_table = JumpTable::new(&obstacles);
+    assert_eq!(Some(10 + 4), jump_table.next_turn[6 * 10 + 4][0]);
+    assert_eq!(None, jump_table.next_turn[6 * 10 + 4][2]);
+    assert_eq!(JumpResult::Exited, jump_table.simulate(start));
+
+    // the jump table has to agree with the cell-by-cell simulation for every obstacle placement
+    let mut num_infinite_loops: usize = 0;
+    for (row, col) in (0..10).flat_map(|row| (0..10).map(move |col| (row, col))) {
+        if obstacles[row][col] || [row, col] == start.pos { continue }
+        let mut new_obstacles: Vec<Vec<bool>> = obstacles.clone();
+        new_obstacles[row][col] = true;
+        let is_loop: bool = matches!(simulate_history(start, &new_obstacles).result, HistoryResult::Infinite { .. });
+
+        jump_table.set_obstacle([row, col], true);
+        assert_eq!(is_loop, jump_table.simulate(start) == JumpResult::Looped);
+        jump_table.set_obstacle([row, col], false);
+        if is_loop { num_infinite_loops += 1 }
+    }
+    assert_eq!(6, num_infinite_loops);
+
+    let fresh_table = JumpTable::new(&obstacles);
+    assert_eq!(fresh_table.next_turn, jump_table.next_turn);
+}
+
+#[test]
+fn test_loop_trace() {
+    let filename: &str = "test_input.txt";
+    let filedata: String = fs::read_to_string(filename).expect("Cannot read file {filename}");
+
+    let mut obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+    let start: Guard = get_start_guard(&filedata);
+
+    // first example obstruction of part II, right next to the starting position
+    obstacles[6][3] = true;
+    let trace: Trace = simulate_history(start, &obstacles);
+    let (start_index, period) = match trace.result {
+        HistoryResult::Infinite { start, period, .. } => (start, period),
+        HistoryResult::Terminated(_) => panic!("guard should be stuck in a loop"),
+    };
+    assert_eq!(trace.guards.len(), start_index + period);
+    assert_eq!(trace.guards.len(), trace.moves.len() + 1);
+    assert_eq!(trace.guards[start_index], trace.guards.last().unwrap().next(&obstacles).unwrap().0);
+    assert_eq!(Guard { pos: [6, 4], facing: Facing::Up }, trace.guards[start_index]);
+    assert!(describe_trace(&trace).ends_with(&format!("loops from move {start_index} with period {period}")));
+}
+
+#[test]
+fn test_loop_obstructions() {
+    let filename: &str = "test_input.txt";
+    let filedata: String = fs::read_to_string(filename).expect("Cannot read file {filename}");
+
+    let obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+    let start: Guard = get_start_guard(&filedata);
+
+    let obstructions: Vec<Obstruction> = find_loop_obstructions(start, &obstacles);
+    let positions: Vec<[usize; 2]> = obstructions.iter().map(|obstruction| obstruction.pos).collect();
+    assert_eq!(vec![[6, 3], [7, 6], [8, 3], [8, 1], [7, 7], [9, 7]], positions);
+    assert!(obstructions.windows(2).all(|pair| pair[0].first_hit < pair[1].first_hit));
+
+    let trace: Trace = simulate_history(start, &obstacles);
+    for Obstruction { pos, first_hit, .. } in obstructions.iter() {
+        assert_eq!(*pos, trace.guards[first_hit+1].pos);
+    }
+
+    let csv: String = obstructions_to_csv(&obstructions);
+    assert_eq!(7, csv.lines().count());
+    assert!(csv.starts_with("row,col,first_hit,loop_length\n"));
+
+    let grid: String = obstructions_to_grid(&filedata, &obstructions);
+    assert_eq!(".#.O^.....", grid.lines().nth(6).unwrap());
+    assert_eq!(6, grid.matches('O').count());
+}
+
+#[test]
+fn test_patrol() {
+    let filename: &str = "test_input.txt";
+    let filedata: String = fs::read_to_string(filename).expect("Cannot read file {filename}");
+
+    let obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+
+    // a single guard turning right is the original puzzle
+    let starts: Vec<Guard> = get_start_guards(&filedata);
+    assert_eq!(vec![get_start_guard(&filedata)], starts);
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::Right], &obstacles);
+    let trace: Trace = simulate_history(starts[0], &obstacles);
+    assert_eq!(vec![GuardStatus::Exited(trace.moves.len() + 1)], result.statuses);
+    assert_eq!(trace.moves.len() + 1, result.num_ticks);
+
+    // turning left (or around) at (1, 4) leads straight off the map
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::Left], &obstacles);
+    assert_eq!(vec![GuardStatus::Exited(11)], result.statuses);
+
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::UTurn], &obstacles);
+    assert_eq!(vec![GuardStatus::Exited(15)], result.statuses);
+}
+
+#[test]
+fn test_patrol_collisions_and_loops() {
+    let filedata: &str = ">.<..\n>..<.\n";
+    let obstacles: Vec<Vec<bool>> = vec![vec![false; 5]; 2];
+    let starts: Vec<Guard> = get_start_guards(filedata);
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::Right; 4], &obstacles);
+    assert_eq!(vec![
+        GuardStatus::Collided(1, vec![1]),
+        GuardStatus::Collided(1, vec![0]),
+        GuardStatus::Collided(2, vec![3]),
+        GuardStatus::Collided(2, vec![2]),
+    ], result.statuses);
+
+    let filedata: &str = ".#....\n.^.>.#\n#.....\n....#.\n";
+    let obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+    let starts: Vec<Guard> = get_start_guards(filedata);
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::Right; 2], &obstacles);
+    assert_eq!(vec![GuardStatus::Looping { start: 0, period: 12 }; 2], result.statuses);
+    assert_eq!(vec![vec![0, 1]], result.shared_loops);
+
+    // the first two guards crash at (0, 1) and stay there, so the third one has to turn below them
+    let filedata: &str = ">.<\n...\n...\n.^.\n";
+    let obstacles: Vec<Vec<bool>> = vec![vec![false; 3]; 4];
+    let starts: Vec<Guard> = get_start_guards(filedata);
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::Right; 3], &obstacles);
+    assert_eq!(vec![
+        GuardStatus::Collided(1, vec![1]),
+        GuardStatus::Collided(1, vec![0]),
+        GuardStatus::Exited(5),
+    ], result.statuses);
+
+    // walled off from each other, the guards loop with periods 6 and 8, and the patrol ends once
+    // both are back at their starts together
+    let filedata: &str = "#>..##\n######\n#>...#\n";
+    let obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+    let starts: Vec<Guard> = get_start_guards(filedata);
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::UTurn; 2], &obstacles);
+    assert_eq!(vec![GuardStatus::Looping { start: 0, period: 6 }, GuardStatus::Looping { start: 0, period: 8 }], result.statuses);
+    assert_eq!(24, result.num_ticks);
+    assert!(result.shared_loops.is_empty());
+
+    // both guards loop on their own within 8 ticks, but meet at (1, 3) two ticks later
+    let filedata: &str = "######\n#>...#\n###.##\n###v##\n######\n";
+    let obstacles: Vec<Vec<bool>> = filedata.lines()
+        .map(|string| string.chars().map(|chr| chr=='#').collect())
+        .collect();
+    let starts: Vec<Guard> = get_start_guards(filedata);
+    let result: PatrolResult = simulate_patrol(&starts, &[TurnRule::UTurn; 2], &obstacles);
+    assert_eq!(vec![GuardStatus::Collided(10, vec![1]), GuardStatus::Collided(10, vec![0])], result.statuses);
+    assert_eq!(10, result.num_ticks);
 }
@@ -1,212 +1,345 @@
 use std::fs;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
 use std::time::Instant;
 
-struct Node {
-    num_add: u64,
-    num_mul: u64,
-    num_cat: u64,
-    nums: Vec<u64>,
-}
-
-impl Node {
-
-    fn yield_add_node(&self) -> Option<Self> {
-        assert!(self.nums.len() >= 2);
-        let mut nums_iter = self.nums.clone().into_iter();
-        let num1: u64 = nums_iter.next().unwrap();
-        let num2: u64 = nums_iter.next().unwrap();
-        let rest_nums: Vec<u64> = nums_iter.collect();
-
-        if self.num_add == 0 { return None }
-        let mut child_add_nums = vec![num1 + num2];
-        child_add_nums.extend(rest_nums.iter());
-        let child_add_node = Node {
-            nums: child_add_nums,
-            num_add: self.num_add - 1,
-            num_mul: self.num_mul,
-            num_cat: self.num_cat,
-        };
-
-        Some(child_add_node)
-    }
-
-    fn yield_mul_node(&self) -> Option<Self> {
-        assert!(self.nums.len() >= 2);
-        let mut nums_iter = self.nums.clone().into_iter();
-        let num1: u64 = nums_iter.next().unwrap();
-        let num2: u64 = nums_iter.next().unwrap();
-        let rest_nums: Vec<u64> = nums_iter.collect();
-
-        if self.num_mul == 0 { return None }
-        let mut child_mul_nums = vec![num1 * num2];
-        child_mul_nums.extend(rest_nums.iter());
-        let child_mul_node = Node {
-            nums: child_mul_nums,
-            num_add: self.num_add,
-            num_mul: self.num_mul - 1,
-            num_cat: self.num_cat,
-        };
-
-        Some(child_mul_node)
-    }
-
-    fn yield_cat_node(&self) -> Option<Self> {
-        assert!(self.nums.len() >= 2);
-        let mut nums_iter = self.nums.clone().into_iter();
-        let num1: u64 = nums_iter.next().unwrap();
-        let num2: u64 = nums_iter.next().unwrap();
-        let rest_nums: Vec<u64> = nums_iter.collect();
-
-        if self.num_cat == 0 { return None }
-        let mut child_cat_nums = vec![
-            (num1.to_string() + num2.to_string().as_str()).parse::<u64>().unwrap()
-        ];
-        child_cat_nums.extend(rest_nums.iter());
-        let child_cat_node = Node {
-            nums: child_cat_nums,
-            num_add: self.num_add,
-            num_mul: self.num_mul,
-            num_cat: self.num_cat - 1,
-        };
-
-        Some(child_cat_node)
-    }
-
-    fn spawn_nodes(&self) -> Vec<Self> {
-        let mut child_nodes: Vec<Node> = Vec::new();
-        if let Some(node) = self.yield_add_node() { child_nodes.push(node) }
-        if let Some(node) = self.yield_mul_node() { child_nodes.push(node) }
-        if let Some(node) = self.yield_cat_node() { child_nodes.push(node) }
-        child_nodes
-    }
-
-}
-
-fn check_target_line(target: u64, nums: &[u64], cat: bool) -> bool {
-
-    // I can optimize these two numbers!
-    let num_mul = (nums.len() - 1) as u64;
-    let num_add = (nums.len() - 1) as u64;
-    let num_cat = if cat {
-        (nums.len() - 1) as u64
-    } else { 0u64 };
-
-    // optimization here, decrease the num_mul, num_add, num_cat as much as possible!
-    let mut num_add = num_add;
-    let mut sorted_nums = nums.to_vec();
-    sorted_nums.sort();
-    sorted_nums.reverse();
-    for num in sorted_nums.iter_mut() {
-        if *num == 1 { *num = 2 }
-    }
-
-    if !cat {  // for no concatenation, limit max. possible additions
-        let mut remove_add = num_mul;
-        for test_num_mul in 0..(nums.len()) {
-            let mul_part = sorted_nums[..test_num_mul].iter().product::<u64>();
-            let add_part = sorted_nums[test_num_mul..].iter().sum::<u64>();
-            let upper_bound: u64 = mul_part * add_part;
-            if upper_bound >= target { 
-                remove_add = test_num_mul as u64;
-                break
-            }
+// everything is checked, an overflowing branch is dropped from the search instead of panicking
+trait Number: Copy + Ord + Display + FromStr + From<u8> {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn checked_div(self, other: Self) -> Option<Self>;
+    fn checked_pow(self, exp: u32) -> Option<Self>;
+    fn checked_ilog(self, base: Self) -> Option<u32>;
+    fn to_u32(self) -> Option<u32>;
+    fn from_u64(num: u64) -> Self;
+}
+
+macro_rules! impl_number {
+    ($($num_type:ty),*) => { $(
+        impl Number for $num_type {
+            fn checked_add(self, other: Self) -> Option<Self> { <$num_type>::checked_add(self, other) }
+            fn checked_sub(self, other: Self) -> Option<Self> { <$num_type>::checked_sub(self, other) }
+            fn checked_mul(self, other: Self) -> Option<Self> { <$num_type>::checked_mul(self, other) }
+            fn checked_div(self, other: Self) -> Option<Self> { <$num_type>::checked_div(self, other) }
+            fn checked_pow(self, exp: u32) -> Option<Self> { <$num_type>::checked_pow(self, exp) }
+            fn checked_ilog(self, base: Self) -> Option<u32> { <$num_type>::checked_ilog(self, base) }
+            fn to_u32(self) -> Option<u32> { self.try_into().ok() }
+            fn from_u64(num: u64) -> Self { num as $num_type }
+        }
+    )* };
+}
+
+impl_number!(u64, u128);
+
+trait Operator<N: Number> {
+    fn symbol(&self) -> String;
+
+    // None if the operator is not defined for these operands (e.g. division by zero, overflow)
+    fn apply(&self, num1: N, num2: N) -> Option<N>;
+
+    // true only if apply(num1, num2) is never smaller than num1, for any num1; once every operator
+    // is monotonic for all the numbers still to come, the search can stop as soon as the target is
+    // exceeded. There is no default, a wrong true drops valid solutions
+    fn is_monotonic(&self, num2: N) -> bool;
+
+    // all num1 with apply(num1, num2) == result, or None if they cannot be listed (or would be too
+    // many); the backward solver then searches forwards for that part of the equation
+    fn unapply(&self, _result: N, _num2: N) -> Option<Vec<N>> { None }
+}
+
+struct Add;
+struct Mul;
+struct Sub;
+struct Div;
+struct Pow;
+struct Cat { base: u64 }
+
+impl<N: Number> Operator<N> for Add {
+    fn symbol(&self) -> String { String::from("+") }
+    fn apply(&self, num1: N, num2: N) -> Option<N> { num1.checked_add(num2) }
+    fn is_monotonic(&self, _num2: N) -> bool { true }
+    fn unapply(&self, result: N, num2: N) -> Option<Vec<N>> { Some(result.checked_sub(num2).into_iter().collect()) }
+}
+
+impl<N: Number> Operator<N> for Mul {
+    fn symbol(&self) -> String { String::from("*") }
+    fn apply(&self, num1: N, num2: N) -> Option<N> { num1.checked_mul(num2) }
+    fn is_monotonic(&self, num2: N) -> bool { num2 >= N::from(1) }  // x * 0 = 0
+    fn unapply(&self, result: N, num2: N) -> Option<Vec<N>> {
+        let zero: N = N::from(0);
+        match result.checked_div(num2) {
+            None if result == zero => None,  // anything times zero
+            None => Some(Vec::new()),  // nothing times zero is anything else
+            Some(num1) => Some((num1.checked_mul(num2) == Some(result)).then_some(num1).into_iter().collect()),
+        }
+    }
+}
+
+impl<N: Number> Operator<N> for Sub {
+    fn symbol(&self) -> String { String::from("-") }
+    fn apply(&self, num1: N, num2: N) -> Option<N> { num1.checked_sub(num2) }
+    fn is_monotonic(&self, num2: N) -> bool { num2 == N::from(0) }
+    fn unapply(&self, result: N, num2: N) -> Option<Vec<N>> { Some(result.checked_add(num2).into_iter().collect()) }
+}
+
+const MAX_DIV_CANDIDATES: u8 = 16;
+
+impl<N: Number> Operator<N> for Div {
+    fn symbol(&self) -> String { String::from("/") }
+    fn apply(&self, num1: N, num2: N) -> Option<N> { num1.checked_div(num2) }
+    fn is_monotonic(&self, num2: N) -> bool { num2 == N::from(1) }
+
+    // result * num2 up to result * num2 + (num2 - 1), as far as they fit. That is num2 candidates
+    // for every division undone, so larger divisors are left to the forward search
+    fn unapply(&self, result: N, num2: N) -> Option<Vec<N>> {
+        if num2 > N::from(MAX_DIV_CANDIDATES) { return None }
+        let mut nums1: Vec<N> = Vec::new();
+        let mut num1: Option<N> = result.checked_mul(num2);
+        while let Some(next) = num1.filter(|&num1| num1.checked_div(num2) == Some(result)) {
+            nums1.push(next);
+            num1 = next.checked_add(N::from(1));
         }
-        num_add -= remove_add;
-    }
-    
-    // note that we could bound the num of adds, muls, and cats independently esp. get a lower
-    // bound for num of cats, and upper bound for num of adds. The other bounds still exist, but
-    // probably don't additionally restrict the bounds.
-    //
-    // if no bounds, then num of possibilities is (3*N choose N) / (N!)^3 ) (excluding pruning)
-    // (this number is wrong! I'm missing a bunch of symmetry factors)
-    //
-    // Each time the bound is restricted by a value, the total number of possibilities decreases by
-    // a factor of (3*N-N-1)/(3*N) ~ 2/3. So this is worthwhile to find!
-
-    let first_node = Node { nums: nums.to_vec(), num_mul, num_add, num_cat};
-
-    // depth-first search
-    let mut stack: Vec<Node> = vec![first_node];
-    let mut found_target: bool = false;
-    assert!(nums.len() >= 2);
-    while let Some(node) = stack.pop() {
-
-        assert!(!node.nums.is_empty());
-        let is_end_node = node.nums.len() == 1;
+        Some(nums1)
+    }
+}
+
+impl<N: Number> Operator<N> for Pow {
+    fn symbol(&self) -> String { String::from("^") }
+    fn apply(&self, num1: N, num2: N) -> Option<N> { num1.checked_pow(num2.to_u32()?) }
+    fn is_monotonic(&self, num2: N) -> bool { num2 >= N::from(1) }  // x^0 = 1
+}
+
+impl<N: Number> Operator<N> for Cat {
+    fn symbol(&self) -> String {
+        if self.base == 10 { String::from("||") } else { format!("||{}", self.base) }
+    }
+
+    // digits of num2 (in the given base) are appended to num1
+    fn apply(&self, num1: N, num2: N) -> Option<N> {
+        num1.checked_mul(self.shift(num2)?)?.checked_add(num2)
+    }
+
+    fn is_monotonic(&self, _num2: N) -> bool { true }
+
+    // strip the digits of num2 off the end of result
+    fn unapply(&self, result: N, num2: N) -> Option<Vec<N>> {
+        let Some(shift) = self.shift(num2) else { return Some(Vec::new()) };
+        let stripped: Option<N> = result.checked_sub(num2)
+            .and_then(|rest| rest.checked_div(shift).filter(|&num1| num1.checked_mul(shift) == Some(rest)));
+        Some(stripped.into_iter().collect())
+    }
+}
+
+impl Cat {
+    // base^(number of digits of num2), zero still has one digit
+    fn shift<N: Number>(&self, num2: N) -> Option<N> {
+        let base: N = N::from_u64(self.base);
+        let num_digits: u32 = num2.checked_ilog(base).map_or(1, |exp| exp + 1);
+        base.checked_pow(num_digits)
+    }
+}
+
+// "+", "*", "-", "/", "^", and "||" for concatenation, optionally followed by a base ("||2")
+fn parse_operator<N: Number>(string: &str) -> Box<dyn Operator<N>> {
+    match string {
+        "+" => Box::new(Add),
+        "*" => Box::new(Mul),
+        "-" => Box::new(Sub),
+        "/" => Box::new(Div),
+        "^" => Box::new(Pow),
+        "||" => Box::new(Cat { base: 10 }),
+        _ => match string.strip_prefix("||").and_then(|base| base.parse::<u64>().ok()) {
+            Some(base) if base >= 2 => Box::new(Cat { base }),
+            _ => panic!("Unknown operator ({string})"),
+        },
+    }
+}
+
+fn parse_operators<N: Number>(string: &str) -> Vec<Box<dyn Operator<N>>> {
+    string.split(',').map(parse_operator).collect()
+}
+
+fn check_target_line<N: Number>(target: N, nums: &[N], operators: &[Box<dyn Operator<N>>]) -> bool {
+    assert!(!nums.is_empty());
+    // prune[i]: every operator is monotonic for all of nums[i..]
+    let mut prune: Vec<bool> = vec![true; nums.len() + 1];
+    for index in (1..nums.len()).rev() {
+        prune[index] = prune[index + 1] && operators.iter().all(|operator| operator.is_monotonic(nums[index]));
+    }
+
+    // depth-first search over (value so far, index of the next number)
+    let mut stack: Vec<(N, usize)> = vec![(nums[0], 1)];
+    while let Some((value, index)) = stack.pop() {
+        let is_end_node = index == nums.len();
         if is_end_node {
-            if node.nums[0] == target { 
-            found_target = true;
-            break
-            } else { continue }
-        } 
-
-        assert!(node.nums.len() >= 2);
-        if node.nums[0] > target { continue } // prune
-        let child_nodes: Vec<Node> = node.spawn_nodes();
-        stack.extend(child_nodes);
+            if value == target { return true } else { continue }
+        }
+
+        if prune[index] && value > target { continue }
+        stack.extend(operators.iter().filter_map(|operator| operator.apply(value, nums[index]).map(|value| (value, index+1))));
+    }
+
+    false
+}
+
+// None if the total overflows, even though every target fits
+fn calibration_sum<N: Number>(textdata: &str, operators: &[Box<dyn Operator<N>>]) -> Option<N> {
+    textdata.lines()
+        .map(string_to_target_nums)
+        .filter(|(target, num)| check_target_line(*target, num, operators))
+        .try_fold(N::from(0), |total, (target, _num)| total.checked_add(target))
+}
+
+// every value of nums evaluated left to right, with the operators used from the first to the last
+fn forward_values<N: Number>(nums: &[N], operators: &[Box<dyn Operator<N>>]) -> Vec<(N, Vec<usize>)> {
+    let mut values: Vec<(N, Vec<usize>)> = vec![(nums[0], Vec::new())];
+    for &num in nums[1..].iter() {
+        values = values.into_iter()
+            .flat_map(|(value, used)| operators.iter().enumerate()
+                .filter_map(move |(i_operator, operator)| {
+                    let mut used: Vec<usize> = used.clone();
+                    used.push(i_operator);
+                    operator.apply(value, num).map(|next| (next, used))
+                })
+            )
+            .collect();
+    }
+    values
+}
+
+// works from the target backwards, undoing the last number each time. Where an operator cannot
+// list what it undoes (anything times zero, large divisors, powers), the numbers before it are
+// searched forwards instead. Expressions are evaluated left to right, e.g. "81 + 40 * 27"
+fn solve_backward<N: Number>(target: N, nums: &[N], operators: &[Box<dyn Operator<N>>]) -> Vec<String> {
+    assert!(!nums.is_empty());
+
+    let mut solutions: Vec<String> = Vec::new();
+    let mut stack: Vec<(N, usize, Vec<usize>)> = vec![(target, nums.len() - 1, Vec::new())];
+    while let Some((value, index, used)) = stack.pop() {
+        if index == 0 {
+            if value == nums[0] { solutions.push(format_equation(nums, operators, &used)) }
+            continue
         }
+        for (i_operator, operator) in operators.iter().enumerate() {
+            let mut used: Vec<usize> = used.clone();
+            used.push(i_operator);
+            match operator.unapply(value, nums[index]) {
+                Some(previous_values) => {
+                    stack.extend(previous_values.into_iter().map(|previous| (previous, index - 1, used.clone())));
+                },
+                None => {
+                    for (previous, forward_used) in forward_values(&nums[..index], operators) {
+                        if operator.apply(previous, nums[index]) != Some(value) { continue }
+                        let mut used: Vec<usize> = used.clone();
+                        used.extend(forward_used.iter().rev());
+                        solutions.push(format_equation(nums, operators, &used));
+                    }
+                },
+            }
+        }
+    }
 
-    found_target
+    solutions.sort();
+    solutions
+}
+
+// operators are given from the last one to the first one
+fn format_equation<N: Number>(nums: &[N], operators: &[Box<dyn Operator<N>>], used: &[usize]) -> String {
+    let mut equation: String = nums[0].to_string();
+    for (&num, &i_operator) in nums[1..].iter().zip(used.iter().rev()) {
+        equation += &format!(" {} {num}", operators[i_operator].symbol());
+    }
+    equation
+}
+
+fn print_solutions<N: Number>(textdata: &str, operators: &[Box<dyn Operator<N>>]) {
+    for (target, nums) in textdata.lines().map(string_to_target_nums::<N>) {
+        let solutions: Vec<String> = solve_backward(target, &nums, operators);
+        if solutions.is_empty() { println!("{target}: no solution") }
+        solutions.iter().for_each(|solution| println!("{target} = {solution}"));
+    }
 }
 
-fn string_to_target_nums(string: &str) -> (u64, Vec<u64>) {
+fn string_to_target_nums<N: Number>(string: &str) -> (N, Vec<N>) {
     let mut iter = string.split_whitespace();
     let mut target = iter.next().unwrap().chars();
     target.next_back();
-    let target: u64 = target.as_str().parse().unwrap();
+    let target: N = parse_number(target.as_str());
 
-    let nums: Vec<u64> = iter
-        .map(|string| string.parse().unwrap())
+    let nums: Vec<N> = iter
+        .map(parse_number)
         .collect();
 
     (target, nums)
 }
 
-fn main() {
-    let filename: &str = "input.txt";
-    let textdata: String = fs::read_to_string(filename)
-        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+fn parse_number<N: Number>(string: &str) -> N {
+    string.parse().unwrap_or_else(|_| panic!("Cannot parse ({string}), try --wide for large numbers"))
+}
 
+fn print_calibration<N: Number>(textdata: &str, label: &str, operators: &str) {
     let before = Instant::now();
 
-    let include_cat = false;
-    let calibration_sum: u64 = textdata.lines()
-        .map(string_to_target_nums)
-        .filter(|(target, num)| check_target_line(*target, num, include_cat))
-        .map(|(target, _num)| target)
-        .sum();
+    let operators: Vec<Box<dyn Operator<N>>> = parse_operators(operators);
+    let calibration_sum: Option<N> = calibration_sum(textdata, &operators);
 
     let after = before.elapsed();
-    println!("Time elapsed (Part  I): {after:2?}");
-    println!("(Part  I): num. of valid cal. sum: {calibration_sum}");
+    println!("Time elapsed ({label}): {after:2?}");
+    match calibration_sum {
+        Some(calibration_sum) => println!("({label}): num. of valid cal. sum: {calibration_sum}"),
+        None => println!("({label}): num. of valid cal. sum overflows, try --wide"),
+    }
+}
 
-    let include_cat = true;
-    let calibration_sum: u64 = textdata.lines()
-        .map(string_to_target_nums)
-        .filter(|(target, num)| check_target_line(*target, num, include_cat))
-        .map(|(target, _num)| target)
-        .sum();
+fn main() {
+    let filename: &str = "input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
 
-    let after = before.elapsed();
-    println!("Time elapsed (Part II): {after:2?}");
-    println!("(Part II): num. of valid cal. sum: {calibration_sum}");
+    // u128 for calibration files with targets beyond u64
+    let wide: bool = env::args().any(|arg| arg == "--wide");
+    let operators_arg: Option<String> = env::args().position(|arg| arg == "--ops").and_then(|i_ops| env::args().nth(i_ops+1));
+
+    if env::args().any(|arg| arg == "--solve") {
+        let operators: &str = operators_arg.as_deref().unwrap_or("+,*,||");
+        if wide {
+            print_solutions::<u128>(&textdata, &parse_operators(operators))
+        } else {
+            print_solutions::<u64>(&textdata, &parse_operators(operators))
+        }
+        return
+    }
+    if let Some(operators) = operators_arg {
+        let label: String = operators.replace(',', " ");
+        if wide { print_calibration::<u128>(&textdata, &label, &operators) } else { print_calibration::<u64>(&textdata, &label, &operators) }
+        return
+    }
+
+    if wide {
+        print_calibration::<u128>(&textdata, "Part  I", "+,*");
+        print_calibration::<u128>(&textdata, "Part II", "+,*,||");
+    } else {
+        print_calibration::<u64>(&textdata, "Part  I", "+,*");
+        print_calibration::<u64>(&textdata, "Part II", "+,*,||");
+    }
 }
 
 #[test]
 fn test_lines() {
-    let include_cat = false;
-    assert!(check_target_line(190, &[10, 19], include_cat));
-    assert!(check_target_line(3267, &[81, 40, 27], include_cat));
-    assert!(!check_target_line(83, &[17, 5], include_cat));
-    assert!(!check_target_line(156, &[15, 6], include_cat));
-    assert!(!check_target_line(7290, &[6, 8, 6, 15], include_cat));
-    assert!(!check_target_line(161011, &[16, 10, 13], include_cat));
-    assert!(!check_target_line(192, &[17, 8, 14], include_cat));
-    assert!(!check_target_line(21037, &[9, 7, 18, 13], include_cat));
-    assert!(check_target_line(292, &[11, 6, 16, 20], include_cat));
-
-    assert!(check_target_line(1585066, &[66, 279, 9, 8, 86], include_cat));
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*");
+    assert!(check_target_line(190, &[10, 19], &operators));
+    assert!(check_target_line(3267, &[81, 40, 27], &operators));
+    assert!(!check_target_line(83, &[17, 5], &operators));
+    assert!(!check_target_line(156, &[15, 6], &operators));
+    assert!(!check_target_line(7290, &[6, 8, 6, 15], &operators));
+    assert!(!check_target_line(161011, &[16, 10, 13], &operators));
+    assert!(!check_target_line(192, &[17, 8, 14], &operators));
+    assert!(!check_target_line(21037, &[9, 7, 18, 13], &operators));
+    assert!(check_target_line(292, &[11, 6, 16, 20], &operators));
+
+    assert!(check_target_line(1585066, &[66, 279, 9, 8, 86], &operators));
 }
 
 #[test]
@@ -215,10 +348,10 @@ fn test_input() {
     let textdata: String = fs::read_to_string(filename)
         .expect(format!("Cannot read file {filename}").as_str());
 
-    let include_cat = false;
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*");
     let num_calibration = textdata.lines()
-        .map(string_to_target_nums)
-        .filter(|(target, num)| check_target_line(*target, num, include_cat))
+        .map(string_to_target_nums::<u64>)
+        .filter(|(target, num)| check_target_line(*target, num, &operators))
         .count();
     assert_eq!(3, num_calibration)
 }
@@ -229,11 +362,102 @@ fn test_cat_input() {
     let textdata: String = fs::read_to_string(filename)
         .expect(format!("Cannot read file {filename}").as_str());
 
-    let include_cat = true;
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*,||");
     let num_calibration = textdata.lines()
-        .map(string_to_target_nums)
-        .filter(|(target, num)| check_target_line(*target, num, include_cat))
+        .map(string_to_target_nums::<u64>)
+        .filter(|(target, num)| check_target_line(*target, num, &operators))
         .count();
     assert_eq!(6, num_calibration)
 }
 
+#[test]
+fn test_operators() {
+    assert_eq!(Some(12345u64), Cat { base: 10 }.apply(12, 345));
+    assert_eq!(Some(110u64), Cat { base: 10 }.apply(11, 0));
+    assert_eq!(Some(0b10111u64), Cat { base: 2 }.apply(0b101, 0b11));
+    assert_eq!(Some(0x1f10u64), Cat { base: 16 }.apply(0x1f, 0x10));
+    assert_eq!(None::<u64>, Div.apply(7, 0));
+    assert_eq!(None::<u64>, Sub.apply(3, 7));
+    assert_eq!("||2", parse_operator::<u64>("||2").symbol());
+
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("-,/");
+    assert!(check_target_line(3, &[20, 5, 1], &operators));  // (20 / 5) - 1
+    assert!(!check_target_line(20, &[20, 5, 1], &operators));
+
+    // 2^3 = 8, and in base 2, 8 || 3 = 1000 || 11 = 100011
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("^,||2");
+    assert!(check_target_line(35, &[2, 3, 3], &operators));
+    assert!(check_target_line(1, &[5, 0], &operators));
+
+    // multiplying by zero makes the value smaller again, so going over the target early is fine
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*");
+    assert!(check_target_line(3, &[5, 4, 0, 3], &operators));  // (5 + 4) * 0 + 3
+    assert!(!Mul.is_monotonic(0u64));
+    assert!(Mul.is_monotonic(1u64));
+}
+
+#[test]
+fn test_solve_backward() {
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*");
+    assert_eq!(vec![String::from("10 * 19")], solve_backward(190, &[10, 19], &operators));
+    assert_eq!(vec![String::from("81 * 40 + 27"), String::from("81 + 40 * 27")], solve_backward(3267, &[81, 40, 27], &operators));
+    assert_eq!(vec![String::from("11 + 6 * 16 + 20")], solve_backward(292, &[11, 6, 16, 20], &operators));
+    assert_eq!(Vec::<String>::new(), solve_backward(83, &[17, 5], &operators));
+
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*,||");
+    assert_eq!(vec![String::from("15 || 6")], solve_backward(156, &[15, 6], &operators));
+    assert_eq!(vec![String::from("6 * 8 || 6 * 15")], solve_backward(7290, &[6, 8, 6, 15], &operators));
+
+    // the backward solver has to agree with the forward search
+    let filename: &str = "test_input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*,||,-,/");
+    for (target, nums) in textdata.lines().map(string_to_target_nums::<u64>) {
+        let solutions: Vec<String> = solve_backward(target, &nums, &operators);
+        assert_eq!(check_target_line(target, &nums, &operators), !solutions.is_empty());
+    }
+
+    // operators which cannot be undone are searched forwards
+    assert_eq!(vec![String::from("2 ^ 3")], solve_backward(8u64, &[2, 3], &parse_operators("^")));
+    assert_eq!(vec![String::from("1 + 2 ^ 2 + 1")], solve_backward(10u64, &[1, 2, 2, 1], &parse_operators("+,^")));
+
+    // anything times zero: "0: 5 0" has a solution, and so does the part before the zero
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*");
+    assert_eq!(vec![String::from("5 * 0")], solve_backward(0, &[5, 0], &operators));
+    assert_eq!(vec![String::from("2 * 3 * 0 + 4"), String::from("2 + 3 * 0 + 4")], solve_backward(4, &[2, 3, 0, 4], &operators));
+    assert_eq!(Vec::<String>::new(), solve_backward(7, &[5, 0], &operators));
+
+    // large divisors go forwards too, with the same solutions
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,/");
+    assert_eq!(None, Div.unapply(3u64, 1000));
+    assert_eq!(vec![String::from("3000 + 999 / 1000 + 1")], solve_backward(4, &[3000, 999, 1000, 1], &operators));
+}
+
+#[test]
+fn test_overflow() {
+    // 10^20 does not fit in a u64, those branches are dropped instead of panicking
+    assert_eq!(None::<u64>, Mul.apply(10_000_000_000, 10_000_000_000));
+    assert_eq!(None::<u64>, Cat { base: 10 }.apply(10_000_000_000_000_000_000, 0));
+    assert_eq!(None::<u64>, Cat { base: 10 }.apply(1, u64::MAX));
+    assert_eq!(Some(Vec::<u64>::new()), Div.unapply(u64::MAX, 2));
+
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*,||");
+    assert!(!check_target_line(u64::MAX, &[u64::MAX / 2, 2, 99], &operators));
+    assert!(check_target_line(u64::MAX, &[u64::MAX / 2, 2, 1], &operators));
+
+    // same line, but with a target beyond u64
+    let line: &str = "36893488147419103230: 18446744073709551615 2";
+    let (target, nums) = string_to_target_nums::<u128>(line);
+    let operators: Vec<Box<dyn Operator<u128>>> = parse_operators("+,*,||");
+    assert!(check_target_line(target, &nums, &operators));
+    assert_eq!(vec![String::from("18446744073709551615 * 2")], solve_backward(target, &nums, &operators));
+    assert_eq!(Some(184467440737095516152u128), Cat { base: 10 }.apply(u64::MAX as u128, 2));
+
+    // every target fits in a u64, their total does not
+    let textdata: String = format!("{0}: {0}\n{0}: {0}\n", u64::MAX);
+    let operators: Vec<Box<dyn Operator<u64>>> = parse_operators("+,*");
+    assert_eq!(None, calibration_sum::<u64>(&textdata, &operators));
+    let operators: Vec<Box<dyn Operator<u128>>> = parse_operators("+,*");
+    assert_eq!(Some(2 * u64::MAX as u128), calibration_sum::<u128>(&textdata, &operators));
+}
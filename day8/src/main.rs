@@ -1,5 +1,6 @@
 use std::fs;
-use std::collections::HashSet;
+use std::env;
+use std::collections::{BTreeMap, HashSet};
 use std::time::Instant;
 
 struct Node {
@@ -7,66 +8,174 @@ struct Node {
     loc: u32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AntinodeMode {
+    // one antinode, twice as far from the first antenna as the second one
+    Single,
+    // every whole multiple of the distance between the antennas
+    Resonant,
+    // every lattice point on the line (the distance is divided by its gcd)
+    Collinear,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Antenna {
+    name: char,
+    pos: Vec<i64>,
+}
+
+fn gcd(num1: i64, num2: i64) -> i64 {
+    let (mut num1, mut num2) = (num1.abs(), num2.abs());
+    while num2 != 0 { (num1, num2) = (num2, num1 % num2) }
+    num1
+}
+
+fn in_bounds(pos: &[i64], size: &[i64]) -> bool {
+    pos.iter().zip(size.iter()).all(|(&coor, &len)| 0 <= coor && coor < len)
+}
+
+// antinodes on the ray from pos1 through pos2, in any number of dimensions; the other side of
+// pos1 comes from the swapped pair. Stepping stops at the edge of the grid
+fn antinodes(pos1: &[i64], pos2: &[i64], size: &[i64], mode: AntinodeMode) -> Vec<Vec<i64>> {
+    assert!(pos1.len() == size.len() && pos2.len() == size.len());
+    let delta: Vec<i64> = pos2.iter().zip(pos1.iter()).map(|(coor2, coor1)| coor2 - coor1).collect();
+    if delta.iter().all(|&coor| coor == 0) { return Vec::new() }
+
+    let (start, step, max_steps): (Vec<i64>, Vec<i64>, usize) = match mode {
+        AntinodeMode::Single => (pos2.iter().zip(delta.iter()).map(|(coor, step)| coor + step).collect(), delta, 1),
+        AntinodeMode::Resonant => (pos1.to_vec(), delta, usize::MAX),
+        AntinodeMode::Collinear => {
+            let divisor: i64 = delta.iter().fold(0, |divisor, &coor| gcd(divisor, coor));
+            (pos1.to_vec(), delta.iter().map(|coor| coor / divisor).collect(), usize::MAX)
+        },
+    };
+
+    let mut found: Vec<Vec<i64>> = Vec::new();
+    let mut pos: Vec<i64> = start;
+    while found.len() < max_steps && in_bounds(&pos, size) {
+        found.push(pos.clone());
+        pos.iter_mut().zip(step.iter()).for_each(|(coor, step)| *coor += step);
+    }
+    found
+}
+
+fn unique_antinodes(antennas: &[Antenna], size: &[i64], mode: AntinodeMode) -> HashSet<Vec<i64>> {
+    antennas.iter()
+        .flat_map(|antenna1| antennas.iter().map(move |antenna2| [antenna1, antenna2]))
+        .filter(|[antenna1, antenna2]| antenna1.name == antenna2.name && antenna1.pos != antenna2.pos)
+        .flat_map(|[antenna1, antenna2]| antinodes(&antenna1.pos, &antenna2.pos, size, mode))
+        .collect()
+}
+
+fn loc_to_pos(loc: u32, size: &[u32; 2]) -> Vec<i64> {
+    vec![(loc / size[1]) as i64, (loc % size[1]) as i64]
+}
+
+fn pos_to_loc(pos: &[i64], size: &[u32; 2]) -> u32 {
+    pos[0] as u32 * size[1] + pos[1] as u32
+}
+
 fn antinode_from_nodes(nodes: &[&Node; 2], size: &[u32; 2]) -> Option<u32> {
     // draw a ray from node1 -> node 2. Make ONE anti-node in that direction.
-    let [num_rows, num_cols] = size;
     let [node1, node2] = nodes;
-    let loc1 = node1.loc;
-    let loc2 = node2.loc;
+    let grid_size: Vec<i64> = size.iter().map(|&len| len as i64).collect();
+    antinodes(&loc_to_pos(node1.loc, size), &loc_to_pos(node2.loc, size), &grid_size, AntinodeMode::Single)
+        .first()
+        .map(|pos| pos_to_loc(pos, size))
+}
 
-    let row1 = loc1 / num_cols;
-    let col1 = loc1 % num_cols;
-    let row2 = loc2 / num_cols;
-    let col2 = loc2 % num_cols;
-    
-    let row3 = row1 as i32 + 2 * (row2 as i32 - row1 as i32);
-    let col3 = col1 as i32 + 2 * (col2 as i32 - col1 as i32);
+fn multiple_antinodes_from_nodes(nodes: &[&Node; 2], size: &[u32; 2]) -> Vec<u32> {
+    // draw a ray from node1 -> node 2, anti-nodes at every multiple of the distance
+    let [node1, node2] = nodes;
+    let grid_size: Vec<i64> = size.iter().map(|&len| len as i64).collect();
+    antinodes(&loc_to_pos(node1.loc, size), &loc_to_pos(node2.loc, size), &grid_size, AntinodeMode::Resonant)
+        .iter()
+        .map(|pos| pos_to_loc(pos, size))
+        .collect()
+}
 
-    let cannot_convert_unsigned = row3 < 0 || col3 < 0 ;
-    if cannot_convert_unsigned { return None }
-    let row3 = row3 as u32;
-    let col3 = col3 as u32;
+// 2D maps separated by blank lines are stacked into a 3D grid, as [layer, row, col]
+fn antennas_from_layers(textdata: &str) -> (Vec<Antenna>, Vec<i64>) {
+    let layers: Vec<Vec<&str>> = textdata.split("\n\n")
+        .map(|layer| layer.lines().filter(|line| !line.is_empty()).collect::<Vec<&str>>())
+        .filter(|layer| !layer.is_empty())
+        .collect();
+    let layer_size: [i64; 2] = [layers[0].len() as i64, layers[0][0].len() as i64];
 
-    let out_bounds = row3 >= *num_rows || col3 >= *num_cols;
-    if out_bounds { return None }
+    let antennas: Vec<Antenna> = layers.iter().enumerate()
+        .flat_map(|(i_layer, layer)| layer.iter().enumerate()
+            .flat_map(move |(row, string)| string.chars().enumerate()
+                .filter(|&(_, chr)| chr != '.')
+                .map(move |(col, name)| Antenna { name, pos: vec![i_layer as i64, row as i64, col as i64] })
+            )
+        )
+        .collect();
 
-    // maybe "row3.try_into()?"
-    let new_loc = row3*num_cols + col3;
-    Some(new_loc)
+    if layers.len() == 1 {
+        let antennas: Vec<Antenna> = antennas.into_iter().map(|Antenna { name, pos }| Antenna { name, pos: pos[1..].to_vec() }).collect();
+        return (antennas, layer_size.to_vec())
+    }
+    (antennas, vec![layers.len() as i64, layer_size[0], layer_size[1]])
 }
 
-fn multiple_antinodes_from_nodes(nodes: &[&Node; 2], size: &[u32; 2]) -> Vec<u32> {
-    // draw a ray from node1 -> node 2. Make ONE anti-node in that direction.
-    let [num_rows, num_cols] = size;
-    let [node1, node2] = nodes;
-    let loc1 = node1.loc;
-    let loc2 = node2.loc;
-
-    let row1 = loc1 / num_cols;
-    let col1 = loc1 % num_cols;
-    let row2 = loc2 / num_cols;
-    let col2 = loc2 % num_cols;
-
-    let mut antinodes: Vec<u32> = Vec::new();
-    let max_steps = *size[..].iter().max().unwrap() as i32;
-    for i in 0..max_steps {  // might be excessive; can cut this down!
-                             // this could also be just an iterator
-        let row3 = row1 as i32 + i * (row2 as i32 - row1 as i32);
-        let col3 = col1 as i32 + i * (col2 as i32 - col1 as i32);
-
-        let cannot_convert_unsigned = row3 < 0 || col3 < 0 ;
-        if cannot_convert_unsigned { continue }
-        let row3 = row3 as u32;
-        let col3 = col3 as u32;
-
-        let out_bounds = row3 >= *num_rows || col3 >= *num_cols;
-        if out_bounds { continue }
-
-        let new_loc = row3*num_cols + col3;
-        antinodes.push(new_loc);
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FrequencyStats {
+    name: char,
+    num_antennas: usize,
+    num_antinodes: usize,
+    num_shared: usize,
+}
+
+fn antinodes_by_frequency(antennas: &[Antenna], size: &[i64], mode: AntinodeMode) -> BTreeMap<char, HashSet<Vec<i64>>> {
+    let mut by_frequency: BTreeMap<char, Vec<Antenna>> = BTreeMap::new();
+    for antenna in antennas.iter() {
+        by_frequency.entry(antenna.name).or_default().push(antenna.clone());
     }
+    by_frequency.iter()
+        .map(|(&name, antennas)| (name, unique_antinodes(antennas, size, mode)))
+        .collect()
+}
+
+// shared antinodes are also an antinode of at least one other frequency
+fn frequency_stats(antennas: &[Antenna], size: &[i64], mode: AntinodeMode) -> Vec<FrequencyStats> {
+    let by_frequency: BTreeMap<char, HashSet<Vec<i64>>> = antinodes_by_frequency(antennas, size, mode);
+    by_frequency.iter()
+        .map(|(&name, antinodes)| {
+            let num_shared: usize = antinodes.iter()
+                .filter(|pos| by_frequency.iter().any(|(&other, others)| other != name && others.contains(*pos)))
+                .count();
+            FrequencyStats {
+                name,
+                num_antennas: antennas.iter().filter(|antenna| antenna.name == name).count(),
+                num_antinodes: antinodes.len(),
+                num_shared,
+            }
+        })
+        .collect()
+}
+
+// same format as the puzzle examples: antennas are drawn over the antinodes, '#' everywhere else
+fn render_antinodes(textdata: &str, mode: AntinodeMode) -> String {
+    let (antennas, size) = antennas_from_layers(textdata);
+    assert_eq!(2, size.len(), "Only 2D maps can be rendered");
 
-    antinodes
+    let mut grid: Vec<Vec<char>> = textdata.lines().filter(|line| !line.is_empty()).map(|line| line.chars().collect()).collect();
+    for pos in unique_antinodes(&antennas, &size, mode) {
+        let cell: &mut char = &mut grid[pos[0] as usize][pos[1] as usize];
+        if *cell == '.' { *cell = '#' }
+    }
+    grid.iter().map(|line| line.iter().collect::<String>() + "\n").collect()
+}
+
+fn print_frequency_stats(textdata: &str) {
+    let (antennas, size) = antennas_from_layers(textdata);
+    for mode in [AntinodeMode::Single, AntinodeMode::Resonant] {
+        println!("{mode:?}:");
+        for FrequencyStats { name, num_antennas, num_antinodes, num_shared } in frequency_stats(&antennas, &size, mode) {
+            println!("    '{name}': {num_antennas} antennas, {num_antinodes} antinodes, {num_shared} shared");
+        }
+        println!("    total: {} unique antinodes", unique_antinodes(&antennas, &size, mode).len());
+    }
 }
 
 fn nodes_from_text(textdata: String) -> Vec<Node>{
@@ -102,6 +211,33 @@ fn main() {
 
     let before = Instant::now();
 
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "--stats") {
+        print_frequency_stats(&textdata);
+        return
+    }
+    if let Some(i_render) = args.iter().position(|arg| arg == "--render") {
+        let mode: AntinodeMode = match args.get(i_render+1).map(String::as_str) {
+            Some("resonant") => AntinodeMode::Resonant,
+            Some("collinear") => AntinodeMode::Collinear,
+            _ => AntinodeMode::Single,
+        };
+        print!("{}", render_antinodes(&textdata, mode));
+        return
+    }
+    if args.iter().any(|arg| arg == "--layers" || arg == "--collinear") {
+        let mode: AntinodeMode = if args.iter().any(|arg| arg == "--collinear") { AntinodeMode::Collinear } else { AntinodeMode::Resonant };
+        let (antennas, size) = antennas_from_layers(&textdata);
+        let num_single: usize = unique_antinodes(&antennas, &size, AntinodeMode::Single).len();
+        let num_multiple: usize = unique_antinodes(&antennas, &size, mode).len();
+
+        let after = before.elapsed();
+        println!("Time elapsed ({}D): {after:2?}", size.len());
+        println!("(Part  I): num. of unique anti-nodes: {num_single}");
+        println!("(Part II, {mode:?}): num. of unique anti-nodes: {num_multiple}");
+        return
+    }
+
     let size = size_from_textdata(&textdata);
 
     let vec_of_nodes: Vec<Node> = nodes_from_text(textdata);
@@ -156,3 +292,60 @@ fn test_input() {
     assert_eq!(14, num_antinodes);
 }
 
+
+#[test]
+fn test_antinodes_nd() {
+    let filename: &str = "test_input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+
+    let (antennas, size) = antennas_from_layers(&textdata);
+    assert_eq!(vec![12, 12], size);
+    assert_eq!(7, antennas.len());
+    assert_eq!(14, unique_antinodes(&antennas, &size, AntinodeMode::Single).len());
+    assert_eq!(34, unique_antinodes(&antennas, &size, AntinodeMode::Resonant).len());
+    assert_eq!(34, unique_antinodes(&antennas, &size, AntinodeMode::Collinear).len());
+
+    // (0, 0) and (2, 4) have a gcd of 2, so (1, 2) lies in between and (3, 6) beyond
+    let size: Vec<i64> = vec![5, 8];
+    assert_eq!(vec![vec![4, 8]], antinodes(&[0, 0], &[2, 4], &[5, 9], AntinodeMode::Single));
+    assert_eq!(vec![vec![0, 0], vec![2, 4], vec![4, 8]], antinodes(&[0, 0], &[2, 4], &[5, 9], AntinodeMode::Resonant));
+    assert_eq!(vec![vec![0, 0], vec![1, 2], vec![2, 4], vec![3, 6]], antinodes(&[0, 0], &[2, 4], &size, AntinodeMode::Collinear));
+    assert_eq!(vec![vec![2, 4], vec![1, 2], vec![0, 0]], antinodes(&[2, 4], &[0, 0], &size, AntinodeMode::Collinear));
+
+    // 3D, stacked from two layers
+    let (antennas, size) = antennas_from_layers("a..\n...\n...\n\n...\n.a.\n...\n");
+    assert_eq!(vec![2, 3, 3], size);
+    assert_eq!(vec![1, 1, 1], antennas[1].pos);
+    assert_eq!(vec![vec![0, 0, 0], vec![1, 1, 1]], antinodes(&antennas[0].pos, &antennas[1].pos, &size, AntinodeMode::Resonant));
+    assert_eq!(0, unique_antinodes(&antennas, &size, AntinodeMode::Single).len());
+
+    let size: Vec<i64> = vec![4, 4, 4, 4];
+    assert_eq!(vec![vec![3, 3, 3, 3]], antinodes(&[1, 1, 1, 1], &[2, 2, 2, 2], &size, AntinodeMode::Single));
+    assert_eq!(3, gcd(-6, 9));
+}
+
+#[test]
+fn test_render_and_stats() {
+    let filename: &str = "test_input.txt";
+    let textdata: String = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot read file {filename}"));
+
+    let expected: &str = "\
+        ......#....#\n...#....0...\n....#0....#.\n..#....0....\n....0....#..\n.#....A.....\n\
+        ...#........\n#......#....\n........A...\n.........A..\n..........#.\n..........#.\n";
+    assert_eq!(expected, render_antinodes(&textdata, AntinodeMode::Single));
+
+    let expected: &str = "\
+        ##....#....#\n.#.#....0...\n..#.#0....#.\n..##...0....\n....0....#..\n.#...#A....#\n\
+        ...#..#.....\n#....#.#....\n..#.....A...\n....#....A..\n.#........#.\n...#......##\n";
+    assert_eq!(expected, render_antinodes(&textdata, AntinodeMode::Resonant));
+
+    // (1, 3) is an antinode of both frequencies
+    let (antennas, size) = antennas_from_layers(&textdata);
+    let stats: Vec<FrequencyStats> = frequency_stats(&antennas, &size, AntinodeMode::Single);
+    assert_eq!(vec![
+        FrequencyStats { name: '0', num_antennas: 4, num_antinodes: 10, num_shared: 1 },
+        FrequencyStats { name: 'A', num_antennas: 3, num_antinodes: 5, num_shared: 1 },
+    ], stats);
+}
@@ -1,19 +1,22 @@
 use std::fs;
+use std::env;
 use std::iter;
 use std::time::Instant;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct SpacePointer {
     loc: usize,
     len: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct DataPointer {
     id: usize,
     loc: usize,
     len: usize,
 }
 
-fn make_pointers_from_disk(disk: &Vec<usize>) -> (Vec<DataPointer>, Vec<SpacePointer>) {
+fn make_pointers_from_disk(disk: &[usize]) -> (Vec<DataPointer>, Vec<SpacePointer>) {
 
     let forward_data_iter = disk.iter()
         .step_by(2)
@@ -35,17 +38,419 @@ fn make_pointers_from_disk(disk: &Vec<usize>) -> (Vec<DataPointer>, Vec<SpacePoi
     (data_locations, spaces)
 }
 
+// max-segment tree over the gap lengths, in disk order; finds the leftmost gap which fits a file
+// in O(log n) instead of scanning all gaps
+struct GapTree {
+    num_leaves: usize,
+    max_len: Vec<usize>,
+}
+
+impl GapTree {
+    fn new(spaces: &[SpacePointer]) -> Self {
+        let num_leaves: usize = spaces.len().next_power_of_two();
+        let mut max_len: Vec<usize> = vec![0; 2 * num_leaves];
+        for (i, space) in spaces.iter().enumerate() { max_len[num_leaves + i] = space.len }
+        for node in (1..num_leaves).rev() { max_len[node] = max_len[2*node].max(max_len[2*node + 1]) }
+        GapTree { num_leaves, max_len }
+    }
+
+    fn leftmost_fitting(&self, len: usize) -> Option<usize> {
+        if self.max_len[1] < len { return None }
+        let mut node: usize = 1;
+        while node < self.num_leaves {
+            node = if self.max_len[2*node] >= len { 2*node } else { 2*node + 1 };
+        }
+        Some(node - self.num_leaves)
+    }
+
+    fn update(&mut self, index: usize, len: usize) {
+        let mut node: usize = self.num_leaves + index;
+        self.max_len[node] = len;
+        while node > 1 {
+            node /= 2;
+            self.max_len[node] = self.max_len[2*node].max(self.max_len[2*node + 1]);
+        }
+    }
+}
+
+// whole files move (highest id first) into the leftmost gap they fit in, if it is to their left.
+// A gap only ever shrinks from its left end, so the gaps stay in disk order
+fn compact_whole_files(data_locations: &mut [DataPointer], spaces: &mut [SpacePointer]) {
+    let mut gaps = GapTree::new(spaces);
+    for data_pointer in data_locations.iter_mut().rev() {
+        let index: usize = match gaps.leftmost_fitting(data_pointer.len) {
+            Some(index) if spaces[index].loc < data_pointer.loc => index,
+            _ => continue,
+        };
+        let space_pointer = &mut spaces[index];
+        data_pointer.loc = space_pointer.loc;
+        space_pointer.loc += data_pointer.len;
+        space_pointer.len -= data_pointer.len;
+        gaps.update(index, space_pointer.len);
+    }
+}
+
+fn pointer_checksum(data_locations: &[DataPointer]) -> usize {
+    data_locations
+        .iter()
+        .flat_map(|pointer| (0..(pointer.len)).map(|i| pointer.id * (pointer.loc + i )))
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MoveStep {
+    id: usize,
+    from: usize,
+    to: usize,
+    len: usize,
+}
+
+// the layout holds one pointer per file fragment, sorted by location
+struct Compaction {
+    layout: Vec<DataPointer>,
+    moves: Vec<MoveStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CompactionMetrics {
+    checksum: usize,
+    num_moves: usize,
+    blocks_moved: usize,
+    fragmented_files: usize,
+    num_gaps: usize,
+}
+
+trait CompactionStrategy {
+    fn name(&self) -> &'static str;
+    fn compact(&self, disk: &[usize]) -> Compaction;
+}
+
+// Part I: blocks from the end of the disk go into the leftmost free block, a file is split over as
+// many gaps as needed
+struct BlockFill;
+// Part II: whole files go into the leftmost gap they fit in
+struct WholeFileLeftmost;
+// whole files go into the smallest gap they fit in (the leftmost of those on ties)
+struct BestFit;
+// whole files go into the leftmost gap they fit in; files which fit nowhere are split instead
+struct FirstFitSplitting;
+// files keep their order and slide left, so that all gaps merge into one at the end of the disk
+struct FileMerging;
+
+fn finish_compaction(mut layout: Vec<DataPointer>, moves: Vec<MoveStep>) -> Compaction {
+    layout.retain(|pointer| pointer.len > 0);
+    layout.sort_by_key(|pointer| pointer.loc);
+    Compaction { layout, moves }
+}
+
+// moves the end of the file into the leftmost gaps before the file, as far as they go; the part
+// which does not fit stays where it is
+fn split_into_gaps(file: DataPointer, spaces: &mut [SpacePointer], layout: &mut Vec<DataPointer>, moves: &mut Vec<MoveStep>) {
+    let mut remaining: usize = file.len;
+    for space in spaces.iter_mut().take_while(|space| space.loc < file.loc) {
+        if remaining == 0 { break }
+        let len: usize = remaining.min(space.len);
+        if len == 0 { continue }
+        remaining -= len;
+        moves.push(MoveStep { id: file.id, from: file.loc + remaining, to: space.loc, len });
+        layout.push(DataPointer { id: file.id, loc: space.loc, len });
+        space.loc += len;
+        space.len -= len;
+    }
+    layout.push(DataPointer { id: file.id, loc: file.loc, len: remaining });
+}
+
+fn move_whole_file(file: &mut DataPointer, space: &mut SpacePointer, moves: &mut Vec<MoveStep>) {
+    moves.push(MoveStep { id: file.id, from: file.loc, to: space.loc, len: file.len });
+    file.loc = space.loc;
+    space.loc += file.len;
+    space.len -= file.len;
+}
+
+impl CompactionStrategy for BlockFill {
+    fn name(&self) -> &'static str { "block-fill" }
+
+    fn compact(&self, disk: &[usize]) -> Compaction {
+        let (data_locations, mut spaces) = make_pointers_from_disk(disk);
+        let mut layout: Vec<DataPointer> = Vec::new();
+        let mut moves: Vec<MoveStep> = Vec::new();
+        for &file in data_locations.iter().rev() {
+            split_into_gaps(file, &mut spaces, &mut layout, &mut moves);
+        }
+        finish_compaction(layout, moves)
+    }
+}
+
+impl CompactionStrategy for WholeFileLeftmost {
+    fn name(&self) -> &'static str { "whole-file-leftmost" }
+
+    fn compact(&self, disk: &[usize]) -> Compaction {
+        let (original, mut spaces) = make_pointers_from_disk(disk);
+        let mut layout: Vec<DataPointer> = original.clone();
+        compact_whole_files(&mut layout, &mut spaces);
+
+        // compact_whole_files goes through the files from the highest id down
+        let moves: Vec<MoveStep> = original.iter().zip(layout.iter()).rev()
+            .filter(|(before, after)| before.loc != after.loc)
+            .map(|(before, after)| MoveStep { id: before.id, from: before.loc, to: after.loc, len: before.len })
+            .collect();
+        finish_compaction(layout, moves)
+    }
+}
+
+impl CompactionStrategy for BestFit {
+    fn name(&self) -> &'static str { "best-fit" }
+
+    fn compact(&self, disk: &[usize]) -> Compaction {
+        let (mut layout, mut spaces) = make_pointers_from_disk(disk);
+        let mut moves: Vec<MoveStep> = Vec::new();
+        for file in layout.iter_mut().rev() {
+            let best_space = spaces.iter_mut()
+                .take_while(|space| space.loc < file.loc)
+                .filter(|space| space.len >= file.len)
+                .min_by_key(|space| (space.len, space.loc));
+            if let Some(space) = best_space { move_whole_file(file, space, &mut moves) }
+        }
+        finish_compaction(layout, moves)
+    }
+}
+
+impl CompactionStrategy for FirstFitSplitting {
+    fn name(&self) -> &'static str { "first-fit-splitting" }
+
+    fn compact(&self, disk: &[usize]) -> Compaction {
+        let (data_locations, mut spaces) = make_pointers_from_disk(disk);
+        let mut layout: Vec<DataPointer> = Vec::new();
+        let mut moves: Vec<MoveStep> = Vec::new();
+        for &file in data_locations.iter().rev() {
+            let first_fit = spaces.iter_mut()
+                .take_while(|space| space.loc < file.loc)
+                .find(|space| space.len >= file.len);
+            match first_fit {
+                Some(space) => {
+                    let mut file: DataPointer = file;
+                    move_whole_file(&mut file, space, &mut moves);
+                    layout.push(file);
+                },
+                None => split_into_gaps(file, &mut spaces, &mut layout, &mut moves),
+            }
+        }
+        finish_compaction(layout, moves)
+    }
+}
+
+impl CompactionStrategy for FileMerging {
+    fn name(&self) -> &'static str { "file-merging" }
+
+    fn compact(&self, disk: &[usize]) -> Compaction {
+        let (mut layout, _) = make_pointers_from_disk(disk);
+        let mut moves: Vec<MoveStep> = Vec::new();
+        let mut loc: usize = 0;
+        for file in layout.iter_mut() {
+            if file.loc != loc {
+                moves.push(MoveStep { id: file.id, from: file.loc, to: loc, len: file.len });
+                file.loc = loc;
+            }
+            loc += file.len;
+        }
+        finish_compaction(layout, moves)
+    }
+}
+
+fn all_strategies() -> Vec<Box<dyn CompactionStrategy>> {
+    vec![Box::new(BlockFill), Box::new(WholeFileLeftmost), Box::new(BestFit), Box::new(FirstFitSplitting), Box::new(FileMerging)]
+}
+
+// gaps are only counted up to the last used block
+fn compaction_metrics(compaction: &Compaction) -> CompactionMetrics {
+    let layout: &[DataPointer] = &compaction.layout;
+    let num_gaps: usize = layout.windows(2)
+        .filter(|pair| pair[0].loc + pair[0].len < pair[1].loc)
+        .count() + usize::from(layout.first().is_some_and(|pointer| pointer.loc > 0));
+
+    let mut ids: Vec<usize> = layout.iter().map(|pointer| pointer.id).collect();
+    ids.sort();
+    let num_fragments: usize = ids.len();
+    ids.dedup();
+
+    CompactionMetrics {
+        checksum: pointer_checksum(layout),
+        num_moves: compaction.moves.len(),
+        blocks_moved: compaction.moves.iter().map(|step| step.len).sum(),
+        fragmented_files: num_fragments - ids.len(),
+        num_gaps,
+    }
+}
+
+fn print_strategies(disk: &[usize]) {
+    for strategy in all_strategies() {
+        let before = Instant::now();
+        let compaction: Compaction = strategy.compact(disk);
+        let after = before.elapsed();
+        let CompactionMetrics { checksum, num_moves, blocks_moved, fragmented_files, num_gaps } = compaction_metrics(&compaction);
+        println!("{:<20} checksum {checksum:>16}, {num_moves} moves ({blocks_moved} blocks), {fragmented_files} extra fragments, {num_gaps} gaps, {after:2?}", strategy.name());
+    }
+}
+
+fn find_strategy(name: &str) -> Box<dyn CompactionStrategy> {
+    all_strategies().into_iter()
+        .find(|strategy| strategy.name() == name)
+        .unwrap_or_else(|| panic!("Unknown strategy ({name})"))
+}
+
+fn print_trace(disk: &[usize], name: &str) {
+    let strategy: Box<dyn CompactionStrategy> = find_strategy(name);
+    let compaction: Compaction = strategy.compact(disk);
+    for MoveStep { id, from, to, len } in compaction.moves.iter() {
+        println!("move file {id}: {len} blocks from {from} to {to}");
+    }
+    for DataPointer { id, loc, len } in compaction.layout.iter() {
+        println!("file {id} at {loc}, {len} blocks");
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiskMapFormat {
+    // one digit per file or gap, as in the puzzle input; file ids follow the order of the files
+    Compact,
+    // comma separated lengths of any size; a file can carry its id as `id:len`
+    Extended,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DiskMapError {
+    InvalidEntry(String),
+    LengthTooLarge(usize),
+    IdOutOfOrder { index: usize, id: usize },
+    Overlap { id: usize, loc: usize },
+}
+
+fn parse_extended_entry(entry: &str) -> Result<(Option<usize>, usize), DiskMapError> {
+    let invalid = || DiskMapError::InvalidEntry(entry.to_string());
+    match entry.split_once(':') {
+        Some((id, len)) => Ok((Some(id.trim().parse().map_err(|_| invalid())?), len.trim().parse().map_err(|_| invalid())?)),
+        None => Ok((None, entry.parse().map_err(|_| invalid())?)),
+    }
+}
+
+// files and gaps alternate, starting with a file; free space after the last file is not kept
+fn decode_disk_map(text: &str, format: DiskMapFormat) -> Result<Vec<DataPointer>, DiskMapError> {
+    let text: &str = text.trim();
+    if text.is_empty() { return Ok(Vec::new()) }
+    let entries: Vec<(Option<usize>, usize)> = match format {
+        DiskMapFormat::Compact => text.chars()
+            .map(|chr| chr.to_digit(10).map(|len| (None, len as usize)).ok_or_else(|| DiskMapError::InvalidEntry(chr.to_string())))
+            .collect::<Result<_, _>>()?,
+        DiskMapFormat::Extended => text.split(',')
+            .map(|entry| parse_extended_entry(entry.trim()))
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut layout: Vec<DataPointer> = Vec::new();
+    let mut loc: usize = 0;
+    for (i, (id, len)) in entries.into_iter().enumerate() {
+        match id {
+            _ if i % 2 == 0 => layout.push(DataPointer { id: id.unwrap_or(i / 2), loc, len }),
+            Some(id) => return Err(DiskMapError::InvalidEntry(format!("{id}:{len}"))),
+            None => (),
+        }
+        loc += len;
+    }
+    Ok(layout)
+}
+
+// one (file id, file length, gap length) entry per fragment; a gap at the start of the disk gets an
+// empty file without id in front of it
+fn disk_entries(layout: &[DataPointer]) -> Result<Vec<(Option<usize>, usize, usize)>, DiskMapError> {
+    let mut fragments: Vec<DataPointer> = layout.to_vec();
+    fragments.sort_by_key(|pointer| pointer.loc);
+
+    let mut entries: Vec<(Option<usize>, usize, usize)> = Vec::new();
+    if let Some(first) = fragments.first().filter(|pointer| pointer.loc > 0) { entries.push((None, 0, first.loc)) }
+    for (i, pointer) in fragments.iter().enumerate() {
+        let end: usize = pointer.loc + pointer.len;
+        let gap: usize = match fragments.get(i + 1) {
+            Some(next) if next.loc < end => return Err(DiskMapError::Overlap { id: next.id, loc: next.loc }),
+            Some(next) => next.loc - end,
+            None => 0,
+        };
+        entries.push((Some(pointer.id), pointer.len, gap));
+    }
+    Ok(entries)
+}
+
+// only works when the fragments are whole files in id order, as a plain disk map has no ids
+fn disk_from_layout(layout: &[DataPointer]) -> Result<Vec<usize>, DiskMapError> {
+    let entries = disk_entries(layout)?;
+    let mut disk: Vec<usize> = Vec::new();
+    for (index, &(id, len, gap)) in entries.iter().enumerate() {
+        if let Some(id) = id.filter(|&id| id != index) { return Err(DiskMapError::IdOutOfOrder { index, id }) }
+        disk.push(len);
+        if index + 1 < entries.len() { disk.push(gap) }
+    }
+    Ok(disk)
+}
+
+fn encode_disk_map(layout: &[DataPointer], format: DiskMapFormat) -> Result<String, DiskMapError> {
+    match format {
+        DiskMapFormat::Compact => disk_from_layout(layout)?.into_iter()
+            .map(|len| char::from_digit(len as u32, 10).filter(|_| len < 10).ok_or(DiskMapError::LengthTooLarge(len)))
+            .collect(),
+        DiskMapFormat::Extended => {
+            let entries = disk_entries(layout)?;
+            let mut lengths: Vec<String> = Vec::new();
+            for (index, &(id, len, gap)) in entries.iter().enumerate() {
+                lengths.push(match id {
+                    Some(id) if id != index => format!("{id}:{len}"),
+                    _ => len.to_string(),
+                });
+                if index + 1 < entries.len() { lengths.push(gap.to_string()) }
+            }
+            Ok(lengths.join(","))
+        },
+    }
+}
+
 fn main() {
     let filename: &str = "input.txt";
     let textdata = fs::read_to_string(filename).unwrap_or_else(|_| panic!("Cannot read file ({filename})"));
 
     let before = Instant::now();
 
-    let disk: Vec<usize> = textdata
-        .lines().next().unwrap()
-        .chars()
-        .map(|chr| chr.to_digit(10).unwrap() as usize)
-        .collect();
+    let args: Vec<String> = env::args().collect();
+    let format: DiskMapFormat = if args.iter().any(|arg| arg == "--extended") { DiskMapFormat::Extended } else { DiskMapFormat::Compact };
+    let layout: Vec<DataPointer> = decode_disk_map(textdata.lines().next().unwrap_or(""), format)
+        .unwrap_or_else(|err| panic!("Cannot decode disk map: {err:?}"));
+    let disk: Vec<usize> = match disk_from_layout(&layout) {
+        Ok(disk) => disk,
+        Err(_) => {
+            // a saved compacted layout: nothing left to compact
+            println!("checksum of the saved layout: {}", pointer_checksum(&layout));
+            return
+        },
+    };
+
+    if args.iter().any(|arg| arg == "--strategies") {
+        print_strategies(&disk);
+        return
+    }
+    if let Some(i_trace) = args.iter().position(|arg| arg == "--trace") {
+        print_trace(&disk, args.get(i_trace+1).expect("--trace needs a strategy name"));
+        return
+    }
+    if let Some(i_encode) = args.iter().position(|arg| arg == "--encode") {
+        let strategy: Box<dyn CompactionStrategy> = find_strategy(args.get(i_encode+1).expect("--encode needs a strategy name"));
+        let out_format: DiskMapFormat = match args.get(i_encode+2).map(|arg| arg.as_str()) {
+            Some("extended") => DiskMapFormat::Extended,
+            _ => DiskMapFormat::Compact,
+        };
+        match encode_disk_map(&strategy.compact(&disk).layout, out_format) {
+            Ok(text) => println!("{text}"),
+            Err(err) => println!("Cannot encode the {} layout: {err:?}", strategy.name()),
+        }
+        return
+    }
+
     let mut compacted: Vec<usize> = Vec::new();
     let (mut data_locations, mut spaces) = make_pointers_from_disk(&disk);
 
@@ -80,28 +485,8 @@ fn main() {
     println!("Time elapsed (Part  I): {after:2?}");
     let before = Instant::now();
 
-    for data_pointer in data_locations.iter_mut().rev() {
-        let data_loc = &mut data_pointer.loc;
-        let data_len = data_pointer.len;
-        let look_for_space = spaces.iter()
-            .take_while(|space| *data_loc > space.loc)
-            .position(|space| data_len <= space.len);
-
-        let (space_pointer, index) = match look_for_space {
-            Some(index) => (&mut spaces[index], index),
-            None => continue,
-        };
-
-        data_pointer.loc = space_pointer.loc;
-        space_pointer.loc += data_len;
-        space_pointer.len -= data_len;
-        if space_pointer.len == 0usize { spaces.remove(index); }
-    }
-
-    let checksum: usize = data_locations
-        .iter()
-        .flat_map(|pointer| (0..(pointer.len)).map(|i| pointer.id * (pointer.loc + i )))
-        .sum();
+    compact_whole_files(&mut data_locations, &mut spaces);
+    let checksum: usize = pointer_checksum(&data_locations);
 
     let after = before.elapsed();
     println!("(Part II) checksum: {checksum}");
@@ -175,3 +560,126 @@ fn test_input_part2() {
         .sum();
     assert_eq!(checksum, 2858);
 }
+
+#[test]
+fn test_gap_tree() {
+    let disk: Vec<usize> = vec![ 2,3,3,3,1,3,3,1,2,1,4,1,4,1,3,1,4,0,2 ];
+    let (mut data_locations, mut spaces) = make_pointers_from_disk(&disk);
+    compact_whole_files(&mut data_locations, &mut spaces);
+    assert_eq!(2858, pointer_checksum(&data_locations));
+
+    // compare against the linear scan on a longer (pseudo-random) disk map
+    let mut state: u64 = 12345;
+    let disk: Vec<usize> = (0..20001)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as usize % 10
+        })
+        .collect();
+    let (mut data_locations, mut spaces) = make_pointers_from_disk(&disk);
+    compact_whole_files(&mut data_locations, &mut spaces);
+
+    let (mut linear_locations, mut linear_spaces) = make_pointers_from_disk(&disk);
+    for data_location in linear_locations.iter_mut().rev() {
+        let look_for_space = linear_spaces.iter()
+            .take_while(|space| data_location.loc > space.loc)
+            .position(|space| data_location.len <= space.len);
+        let Some(index) = look_for_space else { continue };
+        data_location.loc = linear_spaces[index].loc;
+        linear_spaces[index].loc += data_location.len;
+        linear_spaces[index].len -= data_location.len;
+    }
+    assert_eq!(pointer_checksum(&linear_locations), pointer_checksum(&data_locations));
+}
+
+#[test]
+fn test_strategies() {
+    let disk: Vec<usize> = vec![ 2,3,3,3,1,3,3,1,2,1,4,1,4,1,3,1,4,0,2 ];
+
+    let compaction: Compaction = BlockFill.compact(&disk);
+    assert_eq!(1928, pointer_checksum(&compaction.layout));
+    assert_eq!(MoveStep { id: 9, from: 40, to: 2, len: 2 }, compaction.moves[0]);
+    assert_eq!(0, compaction_metrics(&compaction).num_gaps);
+
+    let compaction: Compaction = WholeFileLeftmost.compact(&disk);
+    assert_eq!(2858, pointer_checksum(&compaction.layout));
+    assert_eq!(vec![
+        MoveStep { id: 9, from: 40, to: 2, len: 2 },
+        MoveStep { id: 7, from: 32, to: 8, len: 3 },
+        MoveStep { id: 4, from: 19, to: 12, len: 2 },
+        MoveStep { id: 2, from: 11, to: 4, len: 1 },
+    ], compaction.moves);
+    assert_eq!(0, compaction_metrics(&compaction).fragmented_files);
+
+    // 0...1..233: file 3 goes into the 2-block gap with best fit, into the 3-block one otherwise
+    let small_disk: Vec<usize> = vec![ 1,3,1,2,1,0,2 ];
+    assert_eq!(vec![MoveStep { id: 3, from: 8, to: 5, len: 2 }, MoveStep { id: 2, from: 7, to: 1, len: 1 }, MoveStep { id: 1, from: 4, to: 2, len: 1 }], BestFit.compact(&small_disk).moves);
+    assert_eq!(vec![MoveStep { id: 3, from: 8, to: 1, len: 2 }, MoveStep { id: 2, from: 7, to: 3, len: 1 }], WholeFileLeftmost.compact(&small_disk).moves);
+
+    // 0..111: file 1 fits nowhere whole, so first fit splits it like block fill does
+    let small_disk: Vec<usize> = vec![ 1,2,3 ];
+    let compaction: Compaction = FirstFitSplitting.compact(&small_disk);
+    assert_eq!(vec![MoveStep { id: 1, from: 4, to: 1, len: 2 }], compaction.moves);
+    assert_eq!(1, compaction_metrics(&compaction).fragmented_files);
+    assert_eq!(compaction.layout, BlockFill.compact(&small_disk).layout);
+
+    let compaction: Compaction = FileMerging.compact(&disk);
+    let metrics: CompactionMetrics = compaction_metrics(&compaction);
+    assert_eq!(0, metrics.num_gaps);
+    assert_eq!(0, metrics.fragmented_files);
+    assert_eq!(disk.iter().step_by(2).sum::<usize>(), compaction.layout.last().map(|pointer| pointer.loc + pointer.len).unwrap());
+
+    // applying the moves to the original disk gives the final layout, for every strategy
+    for strategy in all_strategies() {
+        let compaction: Compaction = strategy.compact(&disk);
+        let mut blocks: Vec<Option<usize>> = Vec::new();
+        for (i, &len) in disk.iter().enumerate() {
+            blocks.extend(iter::repeat_n((i % 2 == 0).then_some(i / 2), len));
+        }
+        for MoveStep { id, from, to, len } in compaction.moves.iter() {
+            for i in 0..*len {
+                assert_eq!(Some(*id), blocks[from + i], "{}", strategy.name());
+                assert_eq!(None, blocks[to + i], "{}", strategy.name());
+                blocks[from + i] = None;
+                blocks[to + i] = Some(*id);
+            }
+        }
+        let mut expected: Vec<Option<usize>> = vec![None; blocks.len()];
+        for DataPointer { id, loc, len } in compaction.layout.iter() {
+            (0..*len).for_each(|i| expected[loc + i] = Some(*id));
+        }
+        assert_eq!(expected, blocks, "{}", strategy.name());
+    }
+}
+
+#[test]
+fn test_disk_map_codec() {
+    let text: &str = "2333133121414131402";
+    let layout: Vec<DataPointer> = decode_disk_map(text, DiskMapFormat::Compact).unwrap();
+    let disk: Vec<usize> = vec![ 2,3,3,3,1,3,3,1,2,1,4,1,4,1,3,1,4,0,2 ];
+    assert_eq!(disk, disk_from_layout(&layout).unwrap());
+    assert_eq!(text, encode_disk_map(&layout, DiskMapFormat::Compact).unwrap());
+    let extended: String = encode_disk_map(&layout, DiskMapFormat::Extended).unwrap();
+    assert_eq!("2,3,3,3,1,3,3,1,2,1,4,1,4,1,3,1,4,0,2", extended);
+    assert_eq!(layout, decode_disk_map(&extended, DiskMapFormat::Extended).unwrap());
+
+    // lengths over 9 only fit in the extended format
+    let layout: Vec<DataPointer> = decode_disk_map("12, 0,3 ,15,1", DiskMapFormat::Extended).unwrap();
+    assert_eq!(DataPointer { id: 2, loc: 30, len: 1 }, layout[2]);
+    assert_eq!(Err(DiskMapError::LengthTooLarge(12)), encode_disk_map(&layout, DiskMapFormat::Compact));
+    assert_eq!("12,0,3,15,1", encode_disk_map(&layout, DiskMapFormat::Extended).unwrap());
+
+    // compacted layouts keep their file ids, so they reload with the same checksum
+    let compaction: Compaction = WholeFileLeftmost.compact(&disk);
+    assert_eq!(Err(DiskMapError::IdOutOfOrder { index: 1, id: 9 }), encode_disk_map(&compaction.layout, DiskMapFormat::Compact));
+    let saved: String = encode_disk_map(&compaction.layout, DiskMapFormat::Extended).unwrap();
+    assert_eq!("2,0,9:2,0,1,0,1:3,0,7:3,1,4:2,1,3:3,4,5:4,1,6:4,5,8:4", saved);
+    let reloaded: Vec<DataPointer> = decode_disk_map(&saved, DiskMapFormat::Extended).unwrap();
+    assert_eq!(compaction.layout, reloaded);
+    assert_eq!(2858, pointer_checksum(&reloaded));
+    assert_eq!(saved, encode_disk_map(&reloaded, DiskMapFormat::Extended).unwrap());
+
+    assert_eq!(Err(DiskMapError::InvalidEntry("x".to_string())), decode_disk_map("12x", DiskMapFormat::Compact));
+    assert_eq!(Err(DiskMapError::InvalidEntry("3:4".to_string())), decode_disk_map("1,3:4,1", DiskMapFormat::Extended));
+    assert_eq!(Err(DiskMapError::Overlap { id: 1, loc: 1 }), encode_disk_map(&[DataPointer { id: 0, loc: 0, len: 2 }, DataPointer { id: 1, loc: 1, len: 1 }], DiskMapFormat::Extended));
+}